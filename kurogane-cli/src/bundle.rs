@@ -1,25 +1,14 @@
 use anyhow::{Result, bail};
+use sha2::{Digest, Sha256};
 use std::fs;
-use std::path::PathBuf;
+use std::io::{Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
-pub fn run() -> Result<()> {
-    println!("Bundling application...");
-
-    // Ensure release build
-    let status = Command::new("cargo")
-        .arg("build")
-        .arg("--release")
-        .status()?;
-
-    if !status.success() {
-        bail!("Release build failed");
-    }
-
-    let target = PathBuf::from("target/release");
+use crate::pack;
 
-    // Find executable
-    let exe = find_exe(&target)?;
+pub fn run(pack: bool, integrity: bool) -> Result<()> {
+    println!("Bundling application...");
 
     // Prepare destination
     let dist = PathBuf::from("dist");
@@ -30,16 +19,47 @@ pub fn run() -> Result<()> {
 
     fs::create_dir_all(&dist)?;
 
-    // Copy executable
-    let exe_name = exe.file_name().unwrap();
-    fs::copy(&exe, dist.join(exe_name))?;
-
-    // Copy frontend
+    // Copy or pack frontend; packed first so the build can pin the archive
+    let mut digest = None;
     let content = PathBuf::from("content");
     if content.exists() {
-        copy_dir(&content, &dist.join("content"))?;
+        if pack {
+            // App::path("content") picks up content.kpak when content/ is absent
+            let (count, index_digest) = pack_dir(&content, &dist.join("content.kpak"), integrity)?;
+            println!("Packed {} files into content.kpak", count);
+
+            if let Some(d) = &index_digest {
+                println!("Archive digest: {}", d);
+            }
+            digest = index_digest;
+        } else {
+            copy_dir(&content, &dist.join("content"))?;
+        }
     }
 
+    // Ensure release build. kurogane reads the digest at compile time and
+    // refuses an archive that doesn't match it.
+    let mut build = Command::new("cargo");
+    build.arg("build").arg("--release");
+
+    match &digest {
+        Some(d) => build.env("KUROGANE_ARCHIVE_DIGEST", d),
+        None => build.env_remove("KUROGANE_ARCHIVE_DIGEST"),
+    };
+
+    if !build.status()?.success() {
+        bail!("Release build failed");
+    }
+
+    let target = PathBuf::from("target/release");
+
+    // Find executable
+    let exe = find_exe(&target)?;
+
+    // Copy executable
+    let exe_name = exe.file_name().unwrap();
+    fs::copy(&exe, dist.join(exe_name))?;
+
     // Copy CEF
     let cef_src = find_cef()?;
     let cef_dst = dist.join("cef");
@@ -85,6 +105,88 @@ fn copy_dir(src: &PathBuf, dst: &PathBuf) -> Result<()> {
     Ok(())
 }
 
+/// Write every file below `src` into a single archive; see kurogane/src/pack.rs.
+/// Returns the file count and, with `integrity`, the hex SHA-256 of the
+/// header and index that the runtime checks the archive against.
+fn pack_dir(src: &Path, dst: &Path, integrity: bool) -> Result<(usize, Option<String>)> {
+    let mut files = Vec::new();
+    collect_files(src, src, &mut files)?;
+
+    // The runtime binary searches by path
+    files.sort();
+
+    // Index fields are fixed width, so its length is known before any file is read
+    let placeholder: Vec<pack::Entry> = files
+        .iter()
+        .map(|(rel, _)| pack::Entry {
+            path: rel.clone(),
+            offset: 0,
+            size: 0,
+            sha256: integrity.then_some([0; 32]),
+        })
+        .collect();
+    let index_len = pack::encode(&placeholder).len();
+
+    let mut out = std::io::BufWriter::new(fs::File::create(dst)?);
+    out.seek(SeekFrom::Start(index_len as u64))?;
+
+    // Each file is read once: written to the data section and hashed
+    let mut entries = Vec::with_capacity(files.len());
+    let mut offset = 0;
+
+    for (rel, path) in &files {
+        let data = fs::read(path)?;
+        out.write_all(&data)?;
+
+        entries.push(pack::Entry {
+            path: rel.clone(),
+            offset,
+            size: data.len() as u64,
+            sha256: integrity.then(|| Sha256::digest(&data).into()),
+        });
+
+        offset += data.len() as u64;
+    }
+
+    let index = pack::encode(&entries);
+    if index.len() != index_len {
+        bail!("archive index changed size while packing");
+    }
+
+    out.seek(SeekFrom::Start(0))?;
+    out.write_all(&index)?;
+    out.flush()?;
+
+    let digest = integrity.then(|| {
+        Sha256::digest(&index).iter().map(|b| format!("{:02x}", b)).collect()
+    });
+
+    Ok((files.len(), digest))
+}
+
+/// Collect (archive path, file path) pairs below `dir`.
+fn collect_files(root: &Path, dir: &Path, out: &mut Vec<(String, PathBuf)>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            collect_files(root, &path, out)?;
+            continue;
+        }
+
+        let rel = path
+            .strip_prefix(root)?
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        out.push((rel, path));
+    }
+
+    Ok(())
+}
+
 fn find_cef() -> Result<PathBuf> {
     // Next to exe
     let local = PathBuf::from("cef");
@@ -6,6 +6,9 @@ mod build;
 mod bundle;
 mod init;
 
+#[path = "../../kurogane/src/pack.rs"]
+mod pack;
+
 #[derive(Parser)]
 #[command(name = "kurogane")]
 #[command(about = "Kurogane: Chromium runtime for building high-performance apps", version)]
@@ -19,7 +22,15 @@ enum Commands {
     Install,
     Dev,
     Build,
-    Bundle,
+    Bundle {
+        /// Pack the frontend into a single content.kpak archive
+        #[arg(long)]
+        pack: bool,
+
+        /// Store SHA-256 digests in the archive, checked when files are served
+        #[arg(long, requires = "pack")]
+        integrity: bool,
+    },
     Init {
         name: Option<String>,
 
@@ -35,7 +46,7 @@ fn main() -> anyhow::Result<()> {
         Commands::Install => install::run(),
         Commands::Dev => dev::run(),
         Commands::Build => build::run(),
-        Commands::Bundle => bundle::run(),
+        Commands::Bundle { pack, integrity } => bundle::run(pack, integrity),
         Commands::Init { name, template } => init::run(name, template),
     }
 }
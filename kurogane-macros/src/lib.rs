@@ -0,0 +1,143 @@
+//! Procedural macros for kurogane.
+//!
+//! `include_assets!` embeds a frontend directory into the executable.
+//! MIME types are resolved, ETags computed and compressible files gzipped at
+//! build time, so the app:// handler only has to look files up.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{LitByteStr, LitStr, parse_macro_input};
+
+#[path = "../../kurogane/src/mime.rs"]
+mod mime;
+
+// Smaller files are not worth a Content-Encoding round trip
+const MIN_COMPRESS_SIZE: usize = 1024;
+
+/// Embed a directory as `&'static kurogane::EmbeddedAssets`.
+///
+/// Relative paths are resolved against the calling crate's manifest directory.
+///
+/// ```ignore
+/// App::embedded(kurogane::include_assets!("dist")).run_or_exit();
+/// ```
+#[proc_macro]
+pub fn include_assets(input: TokenStream) -> TokenStream {
+    let lit = parse_macro_input!(input as LitStr);
+
+    match expand(&lit) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn expand(lit: &LitStr) -> syn::Result<proc_macro2::TokenStream> {
+    let root = resolve_root(&lit.value());
+
+    if !root.is_dir() {
+        return Err(syn::Error::new(
+            lit.span(),
+            format!("include_assets!: {} is not a directory", root.display()),
+        ));
+    }
+
+    let mut files = Vec::new();
+    collect(&root, &root, &mut files)
+        .map_err(|e| syn::Error::new(lit.span(), format!("include_assets!: {}", e)))?;
+
+    // The runtime binary searches by path
+    files.sort();
+
+    let mut entries = Vec::new();
+
+    for (rel, abs) in &files {
+        let abs_str = abs.to_string_lossy();
+        let mime = mime::from_path(abs);
+
+        let data = std::fs::read(abs)
+            .map_err(|e| syn::Error::new(lit.span(), format!("include_assets!: {}", e)))?;
+
+        let etag = etag(&data);
+
+        let gzip = match gzip(&data, mime) {
+            Some(bytes) => {
+                let bytes = LitByteStr::new(&bytes, lit.span());
+                quote! { ::core::option::Option::Some(#bytes) }
+            }
+            None => quote! { ::core::option::Option::None },
+        };
+
+        entries.push(quote! {
+            ::kurogane::EmbeddedFile::new(#rel, #mime, include_bytes!(#abs_str), #gzip, #etag)
+        });
+    }
+
+    Ok(quote! {
+        {
+            static ASSETS: ::kurogane::EmbeddedAssets =
+                ::kurogane::EmbeddedAssets::new(&[#(#entries),*]);
+            &ASSETS
+        }
+    })
+}
+
+fn resolve_root(path: &str) -> PathBuf {
+    let path = PathBuf::from(path);
+    if path.is_absolute() {
+        return path;
+    }
+
+    let manifest = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+    PathBuf::from(manifest).join(path)
+}
+
+/// Collect (url path, absolute path) pairs below `dir`.
+fn collect(root: &Path, dir: &Path, out: &mut Vec<(String, PathBuf)>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            collect(root, &path, out)?;
+            continue;
+        }
+
+        let rel = path
+            .strip_prefix(root)
+            .unwrap()
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        out.push((rel, path.canonicalize()?));
+    }
+
+    Ok(())
+}
+
+/// Strong ETag from a 64-bit FNV-1a hash of the contents.
+fn etag(data: &[u8]) -> String {
+    let hash = data.iter().fold(0xcbf2_9ce4_8422_2325u64, |h, b| {
+        (h ^ *b as u64).wrapping_mul(0x0100_0000_01b3)
+    });
+
+    format!("\"{:016x}\"", hash)
+}
+
+/// Gzip compressible files when it saves at least an eighth of the size.
+fn gzip(data: &[u8], mime: &str) -> Option<Vec<u8>> {
+    if !mime::is_compressible(mime) || data.len() < MIN_COMPRESS_SIZE {
+        return None;
+    }
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(data).ok()?;
+    let compressed = encoder.finish().ok()?;
+
+    (compressed.len() < data.len() - data.len() / 8).then_some(compressed)
+}
@@ -2,16 +2,64 @@
 
     if (window.kurogane) return; // prevent double injection
 
+    // IPC protocol version this bridge speaks; must match window.core.protocol
+    const PROTOCOL = 2;
+
+    // Set when the native side speaks another protocol; every call rejects with it
+    const protocolError = window.core.protocol === PROTOCOL
+        ? null
+        : new Error(
+            `kurogane: bridge speaks IPC protocol v${PROTOCOL} but the runtime provides v${window.core.protocol}`
+        );
+
+    if (protocolError !== null) {
+        console.error(protocolError.message);
+    }
+
+    /**
+     * Normalize an ArrayBuffer or ArrayBufferView to a standalone ArrayBuffer.
+     * Returns null for anything else.
+     */
+    function toArrayBuffer(data) {
+        if (data instanceof ArrayBuffer) {
+            return data;
+        }
+
+        if (ArrayBuffer.isView(data)) {
+            // If the input is a typed array or DataView, we cannot just pass its
+            // underlying buffer directly because it may start at a non-zero offset.
+
+            // Slice the buffer to get exactly the bytes this view represents.
+            return data.buffer.slice(
+                data.byteOffset,
+                data.byteOffset + data.byteLength,
+            );
+        }
+
+        return null;
+    }
+
     /**
      * Invoke a named JSON command.
      * Payload is serialized to JSON before sending and the response is deserialized.
      *
+     * When attachments are given the command must be registered with
+     * App::attachment_command, and the result is { value, attachments }.
+     *
      * @param {string} command
      * @param {*} payload - any JSON-serializable value
+     * @param {Array<ArrayBuffer | ArrayBufferView>} [attachments]
      * @returns {Promise<*>}
      */
-    async function invoke(command, payload) {
+    async function invoke(command, payload, attachments) {
+        if (protocolError !== null) throw protocolError;
+
         const json = payload !== undefined ? JSON.stringify(payload) : '';
+
+        if (attachments !== undefined) {
+            return invokeWithAttachments(command, json, attachments);
+        }
+
         const result = await window.core.invoke(command, json);
 
         try {
@@ -21,6 +69,93 @@
         }
     }
 
+    async function invokeWithAttachments(command, json, attachments) {
+        if (!Array.isArray(attachments)) {
+            throw new TypeError("invoke: attachments must be an array");
+        }
+
+        const buffers = attachments.map((a, i) => {
+            const buffer = toArrayBuffer(a);
+            if (buffer === null) {
+                throw new TypeError(`invoke: attachment ${i} is not an ArrayBuffer or ArrayBufferView`);
+            }
+            return buffer;
+        });
+
+        const [meta, out] = await window.core.invokeAttachments(command, json, buffers);
+
+        let value;
+        try {
+            value = JSON.parse(meta);
+        } catch (e) {
+            throw new Error("Invalid JSON response: " + meta);
+        }
+
+        return { value, attachments: out };
+    }
+
+    /**
+     * Invoke several JSON commands in a single round trip.
+     * Calls are dispatched in order on the Rust side; one failing call does not
+     * affect the others. Results follow the Promise.allSettled() shape.
+     *
+     * @param {Array<[string, *]>} calls - list of [command, payload] pairs
+     * @returns {Promise<Array<{status: string, value?: *, reason?: string}>>}
+     */
+    async function batch(calls) {
+        if (protocolError !== null) throw protocolError;
+
+        if (!Array.isArray(calls)) {
+            throw new TypeError("batch: expected an array of [command, payload] pairs");
+        }
+
+        const encoded = calls.map(([command, payload]) => [
+            String(command),
+            payload !== undefined ? JSON.stringify(payload) : '',
+        ]);
+
+        const result = await window.core.invokeBatch(JSON.stringify(encoded));
+
+        try {
+            return JSON.parse(result);
+        } catch (e) {
+            throw new Error("Invalid batch response: " + result);
+        }
+    }
+
+    // dtype names sent by TypedBuffer responses
+    const TYPED_ARRAYS = {
+        u8: Uint8Array,
+        i8: Int8Array,
+        u16: Uint16Array,
+        i16: Int16Array,
+        u32: Uint32Array,
+        i32: Int32Array,
+        u64: BigUint64Array,
+        i64: BigInt64Array,
+        f32: Float32Array,
+        f64: Float64Array,
+    };
+
+    /**
+     * Turn a native binary result into what invokeBinary resolves to.
+     * Plain responses are ArrayBuffers; typed ones arrive as { buffer, dtype, shape }.
+     */
+    function fromBinaryResult(result) {
+        if (result instanceof ArrayBuffer) {
+            return result;
+        }
+
+        const TypedArray = TYPED_ARRAYS[result.dtype];
+        if (TypedArray === undefined) {
+            throw new Error("invokeBinary: unknown dtype " + result.dtype);
+        }
+
+        const view = new TypedArray(result.buffer);
+        Object.defineProperty(view, 'shape', { value: Object.freeze(result.shape) });
+        return view;
+    }
+
     /**
      * Invoke a named binary command.
      *
@@ -28,25 +163,24 @@
      * The native side only understands plain ArrayBuffers, so this wrapper
      * automatically converts or slices the input to a proper ArrayBuffer.
      *
+     * Commands returning a TypedBuffer resolve to the matching typed array
+     * (Float32Array, Int16Array, ...) with a frozen `shape` property.
+     *
+     * With { zeroCopy: true }, large responses delivered over shared memory are
+     * exposed directly instead of being copied into the JS heap. The mapping is
+     * released once the buffer is garbage collected, so avoid holding on to it.
+     *
      * @param {string} command
      * @param {ArrayBuffer | ArrayBufferView} data
-     * @returns {Promise<ArrayBuffer>}
+     * @param {{ zeroCopy?: boolean }} [options]
+     * @returns {Promise<ArrayBuffer | TypedArray>}
      */
-    function invokeBinary(command, data) {
-        let buffer;
+    function invokeBinary(command, data, options) {
+        if (protocolError !== null) return Promise.reject(protocolError);
 
-        if (data instanceof ArrayBuffer) {
-            buffer = data;
-        } else if (ArrayBuffer.isView(data)) {
-            // If the input is a typed array or DataView, we cannot just pass its
-            // underlying buffer directly because it may start at a non-zero offset.
+        const buffer = toArrayBuffer(data);
 
-            // Slice the buffer to get exactly the bytes this view represents.
-            buffer = data.buffer.slice(
-                data.byteOffset,
-                data.byteOffset + data.byteLength,
-            );
-        } else {
+        if (buffer === null) {
             return Promise.reject(
                 new TypeError(
                     `invokeBinary: expected ArrayBuffer or ArrayBufferView, got ${
@@ -56,13 +190,31 @@
             );
         }
 
-        return window.core.invokeBinary(command, buffer);
+        const zeroCopy = options !== undefined && options.zeroCopy === true;
+
+        return window.core.invokeBinary(command, buffer, zeroCopy).then(fromBinaryResult);
+    }
+
+    /**
+     * How zero-copy responses were actually delivered in this renderer.
+     *
+     * V8 builds with the memory sandbox refuse buffers backed by shared memory;
+     * zero-copy responses are then copied and counted in `copied`, and
+     * `supported` turns false.
+     *
+     * @returns {{ delivered: number, copied: number, supported: boolean }}
+     */
+    function zeroCopyStats() {
+        return window.core.zeroCopyStats();
     }
 
     window.kurogane = Object.freeze({
         invoke,
         invokeBinary,
-        version: "0.0.2"
+        batch,
+        zeroCopyStats,
+        version: window.core.version,
+        protocol: PROTOCOL
     });
 
 })();
@@ -6,21 +6,54 @@
 use std::path::PathBuf;
 use serde_json::Value;
 
-use crate::{Runtime, RuntimeError, register_command, register_binary_command};
+use crate::{
+    Runtime,
+    RuntimeError,
+    TypedBuffer,
+    IpcLimits,
+    EmbeddedAssets,
+    Archive,
+    SchemeRequest,
+    SchemeResponse,
+    Action,
+    InterceptedRequest,
+    register_command,
+    register_binary_command,
+    register_attachment_command,
+};
+
+use crate::archive;
+use crate::intercept::{self, Interceptor};
+use crate::har;
+use crate::ipc_limits::set_limits;
+use crate::metrics;
+use crate::scheme::config::{self as scheme_config, extension_key};
+use crate::scheme::glob::Glob;
+use crate::scheme::mount::{self, Mount, MountSource, SchemeFlags};
 
 mod resolver;
 
+use resolver::Assets;
+
 type CommandHandler =
     Box<dyn Fn(Value) -> Result<Value, String> + Send + Sync + 'static>;
 
 type BinaryHandler =
-    Box<dyn Fn(&[u8]) -> Result<Vec<u8>, String> + Send + Sync + 'static>;
+    Box<dyn Fn(&[u8]) -> Result<TypedBuffer, String> + Send + Sync + 'static>;
+
+type AttachmentHandler = Box<
+    dyn Fn(Value, Vec<Vec<u8>>) -> Result<(Value, Vec<Vec<u8>>), String>
+        + Send
+        + Sync
+        + 'static,
+>;
 
 /// Describes where the frontend comes from
 enum Source {
     Name(String),
     Url(String),
     Path(PathBuf),
+    Embedded(&'static EmbeddedAssets),
 }
 
 /// Public application builder.
@@ -30,6 +63,17 @@ pub struct App {
     source: Source,
     commands: Vec<(String, CommandHandler)>,
     binary_commands: Vec<(String, BinaryHandler)>,
+    attachment_commands: Vec<(String, AttachmentHandler)>,
+    limits: Option<IpcLimits>,
+    scheme: scheme_config::Config,
+    /// Serves the frontend; app://app/ unless renamed with origin().
+    mount: Mount,
+    mounts: Vec<Mount>,
+    dev_proxy: Option<String>,
+    interceptors: Vec<(Glob, Interceptor)>,
+    record_har: Option<PathBuf>,
+    replay_har: Option<PathBuf>,
+    archive_digest: Option<String>,
 }
 
 impl App {
@@ -43,28 +87,46 @@ impl App {
             Source::Name(input)
         };
 
-        Self {
-            source,
-            commands: Vec::new(),
-            binary_commands: Vec::new(),
-        }
+        Self::from_source(source)
     }
 
     /// Start from an explicit URL (escape hatch for power users)
     pub fn url(url: impl Into<String>) -> Self {
-        Self {
-            source: Source::Url(url.into()),
-            commands: Vec::new(),
-            binary_commands: Vec::new(),
-        }
+        Self::from_source(Source::Url(url.into()))
     }
 
     /// Load frontend from explicit directory (escape hatch for power users)
     pub fn path(path: impl Into<PathBuf>) -> Self {
+        Self::from_source(Source::Path(path.into()))
+    }
+
+    /// Serve the frontend from assets compiled into the executable.
+    ///
+    /// ```ignore
+    /// App::embedded(kurogane::include_assets!("dist")).run_or_exit();
+    /// ```
+    ///
+    /// CEF_DEV_URL and CEF_APP_PATH still take precedence during development.
+    pub fn embedded(assets: &'static EmbeddedAssets) -> Self {
+        Self::from_source(Source::Embedded(assets))
+    }
+
+    /// Builder with nothing configured yet besides the frontend source.
+    fn from_source(source: Source) -> Self {
         Self {
-            source: Source::Path(path.into()),
+            source,
             commands: Vec::new(),
             binary_commands: Vec::new(),
+            attachment_commands: Vec::new(),
+            limits: None,
+            scheme: scheme_config::Config::default(),
+            mount: Mount::new("app", "app"),
+            mounts: Vec::new(),
+            dev_proxy: None,
+            interceptors: Vec::new(),
+            record_har: None,
+            replay_har: None,
+            archive_digest: archive::PINNED_DIGEST.map(String::from),
         }
     }
 
@@ -77,27 +139,390 @@ impl App {
         self
     }
 
-    pub fn binary_command<F>(
+    /// Register a binary IPC command.
+    ///
+    /// Return `Vec<u8>` to resolve to an ArrayBuffer, or a TypedBuffer
+    /// (e.g. `TypedBuffer::f32(values, [rows, cols])`) to resolve to the
+    /// matching typed array with a `shape` property. A shape that doesn't
+    /// fit the data rejects the call.
+    pub fn binary_command<F, R>(
         mut self,
         name: impl Into<String>,
         handler: F,
     ) -> Self
     where
-        F: Fn(&[u8]) -> Result<Vec<u8>, String> + Send + Sync + 'static,
+        F: Fn(&[u8]) -> Result<R, String> + Send + Sync + 'static,
+        R: Into<TypedBuffer>,
     {
         self.binary_commands
+            .push((name.into(), Box::new(move |data: &[u8]| handler(data).map(Into::into))));
+        self
+    }
+
+    /// Register an IPC command taking JSON metadata plus binary attachments.
+    ///
+    /// Called from JS as `kurogane.invoke(cmd, meta, [buf1, buf2])`, which
+    /// resolves to `{ value, attachments }`.
+    pub fn attachment_command<F>(
+        mut self,
+        name: impl Into<String>,
+        handler: F,
+    ) -> Self
+    where
+        F: Fn(Value, Vec<Vec<u8>>) -> Result<(Value, Vec<Vec<u8>>), String>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.attachment_commands
             .push((name.into(), Box::new(handler)));
         self
     }
 
+    /// Expose IPC metrics to the frontend as a JSON command.
+    ///
+    /// `kurogane.invoke(name)` resolves to the same data as `kurogane::metrics::snapshot()`.
+    pub fn metrics_command(self, name: impl Into<String>) -> Self {
+        self.command(name, |_| {
+            serde_json::to_value(metrics::snapshot()).map_err(|e| e.to_string())
+        })
+    }
+
+    /// Limit IPC payload sizes and outstanding shared memory.
+    ///
+    /// Enforced in both processes; calls over a limit fail with a descriptive error.
+    pub fn limits(mut self, limits: IpcLimits) -> Self {
+        self.limits = Some(limits);
+        self
+    }
+
+    /// Serve files with extension `ext` as `mime` on app://.
+    ///
+    /// Overrides the built-in table for both embedded and on-disk assets.
+    /// Include a charset for text types, e.g. `"text/x-shader; charset=utf-8"`.
+    pub fn mime_type(mut self, ext: &str, mime: impl Into<String>) -> Self {
+        self.scheme.mime_types.insert(extension_key(ext), mime.into());
+        self
+    }
+
+    /// Add response headers to frontend paths matching `pattern`.
+    ///
+    /// `*` matches within a path segment and `**` across segments, so
+    /// `"/**"` covers every path and `"/assets/**/*.js"` scripts under assets.
+    /// Rules apply in the order added; a later rule wins for the same header.
+    ///
+    /// ```ignore
+    /// App::new("demo")
+    ///     .header_rule("/**", [("Content-Security-Policy", "default-src 'self' app:")])
+    ///     .header_rule("/assets/**", [("Cache-Control", "max-age=31536000, immutable")])
+    /// ```
+    pub fn header_rule<I, K, V>(mut self, pattern: &str, headers: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.mount = self.mount.header_rule(pattern, headers);
+        self
+    }
+
+    /// Serve every frontend response with the COOP/COEP headers that make
+    /// the page cross-origin isolated.
+    ///
+    /// Required for SharedArrayBuffer and multithreaded WASM. Cross-origin
+    /// subresources must then opt in with CORS or Cross-Origin-Resource-Policy.
+    pub fn cross_origin_isolated(mut self) -> Self {
+        self.mount = self.mount.cross_origin_isolated();
+        self
+    }
+
+    /// Serve index.html for frontend paths that match no file and have no
+    /// extension, so client-side routes survive reloads and deep links.
+    ///
+    /// Paths with an extension still 404, since they are missing assets.
+    /// Other mounts opt in with Mount::spa_fallback.
+    pub fn spa_fallback(mut self) -> Self {
+        self.mount = self.mount.spa_fallback();
+        self
+    }
+
+    /// Keep paths matching `pattern` out of the SPA fallback, e.g. `"/api/*"`.
+    /// A trailing `/*` covers the whole subtree, `/api/users/1` included.
+    pub fn spa_fallback_exclude(mut self, pattern: &str) -> Self {
+        self.mount = self.mount.spa_fallback_exclude(pattern);
+        self
+    }
+
+    /// Gzip compressible app:// files from disk on first request and keep
+    /// the result in memory.
+    ///
+    /// Precompressed `.br` and `.gz` siblings are always preferred when the
+    /// request accepts them; this covers files the bundler didn't compress.
+    pub fn compress_responses(mut self) -> Self {
+        self.scheme.compress = true;
+        self
+    }
+
+    /// Set Cache-Control for frontend paths matching `pattern`.
+    ///
+    /// The default is `no-cache`: the browser keeps assets but revalidates
+    /// them with their ETag, and unchanged ones are answered with 304.
+    /// Hashed bundle output can be cached for good instead:
+    ///
+    /// ```ignore
+    /// App::new("demo").cache_control("/assets/**", "max-age=31536000, immutable")
+    /// ```
+    pub fn cache_control(mut self, pattern: &str, value: impl Into<String>) -> Self {
+        self.mount = self.mount.cache_control(pattern, value);
+        self
+    }
+
+    /// Disable HTTP caching of app:// responses, for development.
+    ///
+    /// Also enabled by setting `KUROGANE_NO_CACHE=1`.
+    pub fn no_cache(mut self) -> Self {
+        self.scheme.no_cache = true;
+        self
+    }
+
+    /// Answer app:// requests matching `pattern` from Rust.
+    ///
+    /// Reachable from plain `fetch`, `<img src>` and anything else that loads
+    /// a URL. Routes are tried in the order added and take priority over files.
+    /// Handlers run on a worker thread and may block.
+    ///
+    /// ```ignore
+    /// App::new("demo").route("/api/thumbnail", |req: SchemeRequest| {
+    ///     match req.query_param("id") {
+    ///         Some(id) => SchemeResponse::bytes("image/png", render_thumbnail(&id)),
+    ///         None => SchemeResponse::text("missing id").status(400),
+    ///     }
+    /// })
+    /// ```
+    pub fn route<F>(mut self, pattern: &str, handler: F) -> Self
+    where
+        F: Fn(SchemeRequest) -> SchemeResponse + Send + Sync + 'static,
+    {
+        self.mount = self.mount.route(pattern, handler);
+        self
+    }
+
+    /// Serve the frontend from `scheme://host/` instead of `app://app/`.
+    pub fn origin(mut self, scheme: impl Into<String>, host: impl Into<String>) -> Self {
+        let renamed = Mount::new(scheme, host);
+        self.mount.scheme = renamed.scheme;
+        self.mount.host = renamed.host;
+        self
+    }
+
+    /// Register the frontend's scheme with these options instead of the defaults.
+    pub fn scheme_flags(mut self, flags: SchemeFlags) -> Self {
+        self.mount = self.mount.flags(flags);
+        self
+    }
+
+    /// Serve another scheme/host pair, with its own files, routes and flags.
+    ///
+    /// ```ignore
+    /// App::new("demo").mount(Mount::new("media", "library").dir("/home/me/Music"))
+    /// ```
+    pub fn mount(mut self, mount: Mount) -> Self {
+        self.mounts.push(mount);
+        self
+    }
+
+    /// Serve this file from the frontend, e.g. `"404.html"`, with status 404
+    /// when a frontend path matches nothing.
+    pub fn not_found_page(mut self, path: impl Into<String>) -> Self {
+        self.mount = self.mount.not_found_page(path);
+        self
+    }
+
+    /// Render the HTML of app:// error replies, such as 404 or 502, from
+    /// the status and URL. Returning None keeps the default reply.
+    ///
+    /// Takes priority over not_found_page. Runs on an IO worker.
+    ///
+    /// ```ignore
+    /// App::new("demo").error_page(|status, url| {
+    ///     Some(format!("<h1>{}</h1><p>{} could not be loaded.</p>", status, url))
+    /// })
+    /// ```
+    pub fn error_page<F>(mut self, renderer: F) -> Self
+    where
+        F: Fn(u16, &str) -> Option<String> + Send + Sync + 'static,
+    {
+        self.scheme.error_renderer = Some(Box::new(renderer));
+        self
+    }
+
+    /// Serve files and directories whose name starts with a dot.
+    ///
+    /// Hidden paths like `.env` or `.git/` are answered with 404 by default.
+    pub fn allow_dotfiles(mut self) -> Self {
+        self.mount = self.mount.allow_dotfiles();
+        self
+    }
+
+    /// Follow symlinks that point outside the frontend directory.
+    ///
+    /// By default such files are answered with 404, so a stray link can't
+    /// expose the rest of the filesystem.
+    pub fn follow_external_symlinks(mut self) -> Self {
+        self.mount = self.mount.follow_external_symlinks();
+        self
+    }
+
+    /// Require the frontend archive to match `digest`, the hex SHA-256 of its
+    /// index printed by `kurogane bundle --pack --integrity`.
+    ///
+    /// The bundle command already pins it in the executable it builds; use
+    /// this when packing archives another way. With a pin, the frontend must
+    /// come from an archive: a directory in its place is refused.
+    pub fn archive_digest(mut self, digest: impl Into<String>) -> Self {
+        self.archive_digest = Some(digest.into());
+        self
+    }
+
+    /// In debug builds, serve the frontend by forwarding app:// requests to
+    /// a dev server instead of loading its URL directly.
+    ///
+    /// The page keeps its production origin, so storage, CSP and routes
+    /// behave as they will when shipped, while the dev server still
+    /// rebuilds on change. `CEF_DEV_PROXY` does the same in any build.
+    ///
+    /// ```ignore
+    /// App::embedded(kurogane::include_assets!("dist"))
+    ///     .dev_proxy("http://localhost:5173")
+    ///     .run_or_exit();
+    /// ```
+    pub fn dev_proxy(mut self, url: impl Into<String>) -> Self {
+        self.dev_proxy = Some(url.into());
+        self
+    }
+
+    /// Decide what happens to every request the page makes: allow, block,
+    /// redirect, add headers or answer it from Rust.
+    ///
+    /// Interceptors run in the order added, on CEF's IO thread; the first
+    /// answer other than `Action::Allow` wins.
+    ///
+    /// ```ignore
+    /// App::new("demo").on_request(|req: &InterceptedRequest| {
+    ///     if req.url().contains("analytics") { Action::Block } else { Action::Allow }
+    /// })
+    /// ```
+    pub fn on_request<F>(self, interceptor: F) -> Self
+    where
+        F: Fn(&InterceptedRequest) -> Action + Send + Sync + 'static,
+    {
+        self.on_request_for("**", interceptor)
+    }
+
+    /// Like on_request, for URLs matching `pattern` only.
+    ///
+    /// `*` matches within one path segment and `**` across segments:
+    ///
+    /// ```ignore
+    /// App::new("demo")
+    ///     .on_request_for("https://api.example.com/**", |_| {
+    ///         Action::Respond(SchemeResponse::json(&json!({ "items": [] })))
+    ///     })
+    ///     .on_request_for("https://*.tracker.net/**", |_| Action::Block)
+    /// ```
+    pub fn on_request_for<F>(mut self, pattern: &str, interceptor: F) -> Self
+    where
+        F: Fn(&InterceptedRequest) -> Action + Send + Sync + 'static,
+    {
+        self.interceptors.push((Glob::url(pattern), Box::new(interceptor)));
+        self
+    }
+
+    /// Record every request and response, app:// included, to a HAR file.
+    /// `KUROGANE_RECORD_HAR=path` does the same.
+    ///
+    /// Entries are appended as they finish, with bodies captured up to
+    /// 32 MB each, and the file is closed when the app exits.
+    pub fn record_har(mut self, path: impl Into<PathBuf>) -> Self {
+        self.record_har = Some(path.into());
+        self
+    }
+
+    /// Answer http(s) requests from a recorded HAR file instead of the
+    /// network. `KUROGANE_REPLAY_HAR=path` does the same.
+    ///
+    /// Requests missing from the file are blocked, and run() fails once the
+    /// app exits, listing them. on_request interceptors still run first.
+    pub fn replay_har(mut self, path: impl Into<PathBuf>) -> Self {
+        self.replay_har = Some(path.into());
+        self
+    }
+
     /// Start the application
     pub fn run(self) -> Result<(), RuntimeError> {
-        let (asset_root, url) = resolver::resolve(&self.source);
+        let mut frontend = self.mount;
+        let dev_proxy = self.dev_proxy.filter(|_| cfg!(debug_assertions));
+        let (assets, url) = resolver::resolve(&self.source, &frontend.origin(), dev_proxy);
+
+        // Embedded assets need no checks; archives and proxies are validated below
+        let require_assets = matches!(assets, Some(Assets::Dir(_)));
+
+        match assets {
+            // A directory next to the executable must not replace a pinned archive
+            Some(Assets::Dir(root)) if self.archive_digest.is_some() => {
+                return Err(RuntimeError::ArchiveInvalid(
+                    root,
+                    "the executable pins an archive digest, but the frontend is a directory".into(),
+                ));
+            }
+            Some(Assets::Dir(root)) => {
+                Runtime::set_asset_root(root)?;
+                frontend.source = Some(MountSource::Dir(Runtime::asset_root()));
+            }
+            Some(Assets::Embedded(assets)) => {
+                frontend.source = Some(MountSource::Embedded(assets));
+            }
+            Some(Assets::Archive(path)) => {
+                let archive = match &self.archive_digest {
+                    Some(digest) => Archive::open_pinned(path, digest)?,
+                    None => Archive::open(path)?,
+                };
+                frontend.source = Some(MountSource::Archive(archive));
+            }
+            Some(Assets::Proxy(target)) => {
+                frontend = frontend.proxy(target).canonicalize()?;
+            }
+            None => {}
+        }
 
-        let require_assets = asset_root.is_some();
+        // Registered as custom schemes in every process, so set before Runtime::run
+        let mut mounts = vec![frontend];
+        for extra in self.mounts {
+            mounts.push(extra.canonicalize()?);
+        }
+        mount::set_mounts(mounts);
 
-        if let Some(root) = asset_root {
-            Runtime::set_asset_root(root)?;
+        // Set before anything touches IPC so every process sees the same limits
+        if let Some(limits) = self.limits {
+            set_limits(limits);
+        }
+
+        let mut scheme = self.scheme;
+        scheme.no_cache |= std::env::var("KUROGANE_NO_CACHE").is_ok_and(|v| v == "1");
+
+        scheme_config::set_config(scheme);
+        intercept::set_interceptors(self.interceptors);
+
+        // The request hooks run in the browser process; subprocesses would
+        // only truncate the recording or parse the replay file again
+        if Runtime::is_browser_process() {
+            if let Some(path) = std::env::var_os("KUROGANE_RECORD_HAR").map(PathBuf::from).or(self.record_har) {
+                har::record(path)?;
+            }
+
+            if let Some(path) = std::env::var_os("KUROGANE_REPLAY_HAR").map(PathBuf::from).or(self.replay_har) {
+                har::replay(path)?;
+            }
         }
 
         for (name, handler) in self.commands {
@@ -108,7 +533,13 @@ impl App {
             register_binary_command(name, handler);
         }
 
-        Runtime::run(url, require_assets)
+        for (name, handler) in self.attachment_commands {
+            register_attachment_command(name, handler);
+        }
+
+        Runtime::run(url, require_assets)?;
+
+        har::finish()
     }
 
     /// Run the application and terminate the process on failure.
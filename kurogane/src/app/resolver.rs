@@ -1,21 +1,42 @@
 //! Internal frontend resolution logic.
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use cef::CefString;
 
+use crate::EmbeddedAssets;
 use super::Source;
 
-/// Resolve the frontend entrypoint.
+/// What app:// serves.
+pub(crate) enum Assets {
+    Dir(PathBuf),
+    Embedded(&'static EmbeddedAssets),
+    Archive(PathBuf),
+    /// Dev server that app:// forwards to.
+    Proxy(String),
+}
+
+/// Resolve the frontend entrypoint. `origin` is the frontend mount's
+/// `scheme://host/`.
 ///
 /// Returns:
-/// (optional asset root, start URL)
+/// (optional assets, start URL)
 ///
 /// Priority:
 /// 1. CEF_DEV_URL (live dev server)
-/// 2. CEF_APP_PATH (custom frontend directory)
-/// 3. examples/<name>/index.html (cargo run)
-/// 4. assets/index.html next to the executable (release)
-pub(crate) fn resolve(source: &Source) -> (Option<PathBuf>, CefString) {
+/// 2. CEF_DEV_PROXY, then App::dev_proxy (dev server behind app://)
+/// 3. CEF_APP_PATH (custom frontend directory)
+/// 4. assets embedded with include_assets!
+/// 5. examples/<name>/index.html (cargo run)
+/// 6. assets.kpak or assets/index.html next to the executable (release)
+///
+/// A directory that doesn't exist is replaced by a `.kpak` archive of the
+/// same name, so `App::path("content")` also serves a packed bundle.
+pub(crate) fn resolve(
+    source: &Source,
+    origin: &str,
+    dev_proxy: Option<String>,
+) -> (Option<Assets>, CefString) {
+    let index = CefString::from(format!("{}index.html", origin).as_str());
 
     // Explicit URL (dev server or remote site)
     if let Source::Url(url) = source {
@@ -27,15 +48,23 @@ pub(crate) fn resolve(source: &Source) -> (Option<PathBuf>, CefString) {
         return (None, CefString::from(url.as_str()));
     }
 
+    // Dev server behind the app origin; it serves its own index at /
+    if let Some(target) = std::env::var("CEF_DEV_PROXY").ok().or(dev_proxy) {
+        return (Some(Assets::Proxy(target)), CefString::from(origin));
+    }
+
     // Explicit directory override
     if let Ok(path) = std::env::var("CEF_APP_PATH") {
-        let dir = PathBuf::from(path);
-        return (Some(dir), CefString::from("app://app/index.html"));
+        return (Some(dir_or_archive(Path::new(&path))), index);
     }
 
     match source {
+        Source::Embedded(assets) => {
+            (Some(Assets::Embedded(*assets)), index)
+        }
+
         Source::Path(dir) => {
-            return (Some(dir.clone()), CefString::from("app://app/index.html"));
+            (Some(dir_or_archive(dir)), index)
         }
 
         Source::Name(name) => {
@@ -46,7 +75,7 @@ pub(crate) fn resolve(source: &Source) -> (Option<PathBuf>, CefString) {
                     .join(name);
 
                 if dir.join("index.html").exists() {
-                    return (Some(dir), CefString::from("app://app/index.html"));
+                    return (Some(Assets::Dir(dir)), index);
                 }
             }
 
@@ -54,9 +83,23 @@ pub(crate) fn resolve(source: &Source) -> (Option<PathBuf>, CefString) {
             let exe = std::env::current_exe().unwrap();
             let dir = exe.parent().unwrap().join("assets");
 
-            (Some(dir), CefString::from("app://app/index.html"))
+            (Some(dir_or_archive(&dir)), index)
         }
 
         Source::Url(_) => unreachable!(),
     }
 }
+
+/// `path` itself if it is an archive or an existing directory,
+/// otherwise `path.kpak` if that exists.
+fn dir_or_archive(path: &Path) -> Assets {
+    let packed = path.with_extension("kpak");
+
+    if path.is_file() {
+        Assets::Archive(path.to_path_buf())
+    } else if !path.exists() && packed.is_file() {
+        Assets::Archive(packed)
+    } else {
+        Assets::Dir(path.to_path_buf())
+    }
+}
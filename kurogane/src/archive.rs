@@ -0,0 +1,161 @@
+//! Read side of packed asset archives; the format is in pack.rs.
+//!
+//! The archive is memory-mapped. Archives without digests serve files as
+//! slices of the map. Archives with SHA-256 digests copy each file out of
+//! the map and check the copy the first time it is served, then keep it,
+//! so bytes changed on disk after opening are refused rather than served
+//! and range requests don't hash the whole file again.
+//!
+//! The digests live in the archive itself, so they only detect corruption
+//! on their own. `kurogane bundle --pack --integrity` also pins a digest of
+//! the index in the executable; an archive opened against that pin can't
+//! be edited without the edit being detected.
+
+use std::fs::File;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+
+use memmap2::Mmap;
+use sha2::{Digest, Sha256};
+
+use crate::error::RuntimeError;
+use crate::pack::{self, Entry};
+
+/// Digest of the archive index the bundle CLI pins in the executable, as
+/// 64 hex characters. Set by `kurogane bundle --pack --integrity`.
+pub(crate) const PINNED_DIGEST: Option<&str> = option_env!("KUROGANE_ARCHIVE_DIGEST");
+
+/// A `.kpak` archive produced by `kurogane bundle --pack`.
+pub struct Archive {
+    path: PathBuf,
+    map: Arc<Mmap>,
+    data_start: usize,
+    entries: Vec<Entry>,
+    /// Checked copy of each entry, by index, once it has been served.
+    verified: Vec<OnceLock<Arc<[u8]>>>,
+}
+
+impl Archive {
+    /// Map an archive and read its index.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, RuntimeError> {
+        let path = path.as_ref().to_path_buf();
+        let invalid = |reason: String| RuntimeError::ArchiveInvalid(path.clone(), reason);
+
+        let file = File::open(&path).map_err(|_| RuntimeError::AssetRootMissing(path.clone()))?;
+
+        // SAFETY: the archive is read-only application data. Files with
+        // digests are copied out and checked once, and the copy is served
+        // from then on, so a change made while mapped is refused; without
+        // digests it is served as is.
+        let map = unsafe { Mmap::map(&file) }.map_err(|e| invalid(e.to_string()))?;
+
+        let (entries, data_start) = pack::decode(&map).map_err(invalid)?;
+
+        let verified = entries.iter().map(|_| OnceLock::new()).collect();
+
+        Ok(Self { path, map: Arc::new(map), data_start, entries, verified })
+    }
+
+    /// Map an archive whose header and index must hash to `digest` (hex
+    /// SHA-256, as printed by `kurogane bundle --pack --integrity`). The
+    /// index holds each file's digest, so every file is pinned as well.
+    pub fn open_pinned(path: impl AsRef<Path>, digest: &str) -> Result<Self, RuntimeError> {
+        let archive = Self::open(path)?;
+        let invalid = |reason: &str| RuntimeError::ArchiveInvalid(archive.path.clone(), reason.to_string());
+
+        let expected = parse_digest(digest).ok_or_else(|| invalid("the pinned digest is not 64 hex characters"))?;
+        let actual: [u8; 32] = Sha256::digest(&archive.map[..archive.data_start]).into();
+
+        if actual != expected {
+            return Err(invalid("the archive does not match the digest pinned in the executable"));
+        }
+
+        if !archive.has_digests() && !archive.entries.is_empty() {
+            return Err(invalid("the archive carries no file digests; pack it with --integrity"));
+        }
+
+        Ok(archive)
+    }
+
+    /// Whether files are checked against SHA-256 digests.
+    pub fn has_digests(&self) -> bool {
+        self.entries.first().is_some_and(|e| e.sha256.is_some())
+    }
+
+    /// Locate `path`. Errors if its digest does not match.
+    pub(crate) fn get(&self, path: &str) -> Option<Result<ArchiveFile, String>> {
+        let i = self
+            .entries
+            .binary_search_by(|e| e.path.as_str().cmp(path))
+            .ok()?;
+
+        let entry = &self.entries[i];
+        let start = self.data_start + entry.offset as usize;
+        let range = start..start + entry.size as usize;
+
+        let Some(expected) = &entry.sha256 else {
+            return Some(Ok(ArchiveFile {
+                data: ArchiveData::Mapped(self.map.clone(), range),
+                etag: format!("\"{:x}-{:x}\"", entry.offset, entry.size),
+            }));
+        };
+
+        let etag = format!("\"{}\"", hex(&expected[..8]));
+
+        if let Some(data) = self.verified[i].get() {
+            return Some(Ok(ArchiveFile { data: ArchiveData::Verified(data.clone()), etag }));
+        }
+
+        // Check a copy: the map can change under us, the copy can't. A
+        // mismatch isn't kept, so the file stays refused on every request
+        let data: Arc<[u8]> = Arc::from(&self.map[range]);
+        let actual: [u8; 32] = Sha256::digest(&data).into();
+
+        if actual != *expected {
+            return Some(Err(format!(
+                "integrity check failed for {} in {}: the archive was modified after bundling",
+                entry.path,
+                self.path.display()
+            )));
+        }
+
+        // Two requests racing here both hash; the first copy wins
+        let data = self.verified[i].get_or_init(|| data).clone();
+
+        Some(Ok(ArchiveFile {
+            data: ArchiveData::Verified(data),
+            etag,
+        }))
+    }
+}
+
+/// A file inside an archive, checked against its digest if it has one.
+pub(crate) struct ArchiveFile {
+    pub data: ArchiveData,
+    pub etag: String,
+}
+
+pub(crate) enum ArchiveData {
+    /// Slice of the map, for archives without digests.
+    Mapped(Arc<Mmap>, Range<usize>),
+    /// Copy whose digest matched, shared by every request for the file.
+    Verified(Arc<[u8]>),
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn parse_digest(hex: &str) -> Option<[u8; 32]> {
+    let hex = hex.trim();
+    if hex.len() != 64 || !hex.is_ascii() {
+        return None;
+    }
+
+    let mut out = [0u8; 32];
+    for (i, byte) in out.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(out)
+}
@@ -16,8 +16,8 @@ wrap_browser_process_handler! {
         window: Arc<Mutex<Option<Window>>>,
         start_url: CefString,
 
-        // Keep factory alive for browser lifetime; RefCell for interior mutability
-        scheme_factory: RefCell<Option<SchemeHandlerFactory>>,
+        // Keep factories alive for browser lifetime; RefCell for interior mutability
+        scheme_factories: RefCell<Vec<SchemeHandlerFactory>>,
         window_creation_started: Arc<AtomicBool>,
     }
 
@@ -30,25 +30,27 @@ wrap_browser_process_handler! {
             debug!("IPC dispatcher initialized");
 
             // Register once per request context
-            if self.scheme_factory.borrow().is_none() {
-                debug!("Registering scheme handler factory for app://");
+            if self.scheme_factories.borrow().is_empty() {
+                let global = request_context_get_global_context().unwrap();
 
-                // create factory (temporary mutable)
-                let mut factory = crate::scheme::AppSchemeHandlerFactory::new();
+                // One factory per mount, e.g. app://app/ and media://library/
+                for mount in crate::scheme::mount::mounts() {
+                    debug!("Registering scheme handler factory for {}", mount.origin());
 
-                // Register the scheme handler factory for app:// URLs
-                let global = request_context_get_global_context().unwrap();
+                    // create factory (temporary mutable)
+                    let mut factory = crate::scheme::AppSchemeHandlerFactory::new(mount);
 
-                let result = global.register_scheme_handler_factory(
-                    Some(&CefString::from("app")),
-                    Some(&CefString::from("app")),
-                    Some(&mut factory),
-                );
+                    let result = global.register_scheme_handler_factory(
+                        Some(&CefString::from(mount.scheme.as_str())),
+                        Some(&CefString::from(mount.host.as_str())),
+                        Some(&mut factory),
+                    );
 
-                // store so CEF never calls freed memory
-                *self.scheme_factory.borrow_mut() = Some(factory);
+                    // store so CEF never calls freed memory
+                    self.scheme_factories.borrow_mut().push(factory);
 
-                debug!("register_scheme_handler_factory result: {}", result);
+                    debug!("register_scheme_handler_factory result: {}", result);
+                }
             }
 
             // Atomically claim the window creation slot; bail if already taken
@@ -9,7 +9,7 @@ use crate::browser::DemoBrowserProcessHandler;
 use crate::ipc_renderer::IpcRenderProcessHandler;
 use crate::debug;
 
-use cef::sys::cef_scheme_options_t::*;
+use crate::scheme::mount::{SchemeFlags, mounts};
 
 wrap_app! {
     pub struct DemoApp {
@@ -69,18 +69,24 @@ wrap_app! {
 
             let registrar = registrar.unwrap();
 
-            let flags =
-                CEF_SCHEME_OPTION_STANDARD as i32 |
-                CEF_SCHEME_OPTION_SECURE as i32 |
-                CEF_SCHEME_OPTION_CORS_ENABLED as i32 |
-                CEF_SCHEME_OPTION_FETCH_ENABLED as i32;
+            // Each custom scheme once, with the flags of all its mounts
+            let mut schemes: Vec<(&str, SchemeFlags)> = Vec::new();
 
-            let result = registrar.add_custom_scheme(
-                Some(&CefString::from("app")),
-                flags,
-            );
+            for mount in mounts().iter().filter(|m| !m.is_builtin_scheme()) {
+                match schemes.iter_mut().find(|(s, _)| *s == mount.scheme) {
+                    Some((_, flags)) => *flags = *flags | mount.flags,
+                    None => schemes.push((&mount.scheme, mount.flags)),
+                }
+            }
+
+            for (scheme, flags) in schemes {
+                let result = registrar.add_custom_scheme(
+                    Some(&CefString::from(scheme)),
+                    flags.bits(),
+                );
 
-            debug!("Registered 'app://' scheme with flags {} result: {}", flags, result);
+                debug!("Registered '{}://' scheme with flags {} result: {}", scheme, flags.bits(), result);
+            }
         }
 
         fn browser_process_handler(&self) -> Option<BrowserProcessHandler> {
@@ -88,7 +94,7 @@ wrap_app! {
                 DemoBrowserProcessHandler::new(
                     self.window.clone(),
                     self.start_url.clone(),
-                    RefCell::new(None),
+                    RefCell::new(Vec::new()),
                     self.window_creation_started.clone(),
                 )
             )
@@ -2,6 +2,8 @@
 
 use cef::*;
 use crate::debug;
+use crate::intercept::{self, InterceptRequestHandler};
+use crate::load_error;
 
 //
 // LOAD HANDLER
@@ -37,7 +39,7 @@ wrap_load_handler! {
         fn on_load_error(
             &self,
             _browser: Option<&mut Browser>,
-            _frame: Option<&mut Frame>,
+            frame: Option<&mut Frame>,
             error_code: Errorcode,
             error_text: Option<&CefString>,
             failed_url: Option<&CefString>,
@@ -45,6 +47,46 @@ wrap_load_handler! {
             let err = error_text.map(|s| s.to_string()).unwrap_or_default();
             let url = failed_url.map(|s| s.to_string()).unwrap_or_default();
             debug!("[LoadHandler] ERROR {:?} '{}' {}", error_code, err, url);
+
+            // The error page itself failed; loading it again would loop
+            if url.starts_with("data:") {
+                return;
+            }
+
+            // Replace Chromium's blank error page with one that can retry
+            let code = *error_code.as_ref() as i32;
+
+            if let (Some(frame), Some(page)) = (frame, load_error::page_url(&url, code, &err)) {
+                frame.load_url(Some(&CefString::from(page.as_str())));
+            }
+        }
+    }
+}
+
+//
+// LIFE SPAN / FRAME HANDLERS
+// Release IPC state held for browsers and frames that go away
+//
+wrap_life_span_handler! {
+    pub struct DemoLifeSpanHandler;
+
+    impl LifeSpanHandler {
+        fn on_before_close(&self, browser: Option<&mut Browser>) {
+            if let Some(browser) = browser {
+                crate::ipc_browser::release_browser(browser);
+            }
+        }
+    }
+}
+
+wrap_frame_handler! {
+    pub struct DemoFrameHandler;
+
+    impl FrameHandler {
+        fn on_frame_detached(&self, browser: Option<&mut Browser>, frame: Option<&mut Frame>) {
+            if let (Some(browser), Some(frame)) = (browser, frame) {
+                crate::ipc_browser::release_frame(browser, frame);
+            }
         }
     }
 }
@@ -60,6 +102,23 @@ wrap_client! {
             Some(DemoLoadHandler::new())
         }
 
+        fn life_span_handler(&self) -> Option<LifeSpanHandler> {
+            Some(DemoLifeSpanHandler::new())
+        }
+
+        fn frame_handler(&self) -> Option<FrameHandler> {
+            Some(DemoFrameHandler::new())
+        }
+
+        fn request_handler(&self) -> Option<RequestHandler> {
+            // Without interceptors CEF skips the per-request hooks entirely
+            if !intercept::active() {
+                return None;
+            }
+
+            Some(InterceptRequestHandler::new())
+        }
+
         fn on_process_message_received(
             &self,
             browser: Option<&mut Browser>,
@@ -0,0 +1,75 @@
+//! Frontend assets compiled into the executable.
+//!
+//! Built by `include_assets!`, which resolves MIME types, hashes contents
+//! and gzips compressible files at build time. Served by the app:// handler.
+
+/// A directory tree embedded with `include_assets!`.
+pub struct EmbeddedAssets {
+    // Sorted by path
+    files: &'static [EmbeddedFile],
+}
+
+/// One embedded file.
+pub struct EmbeddedFile {
+    path: &'static str,
+    mime: &'static str,
+    data: &'static [u8],
+    gzip: Option<&'static [u8]>,
+    etag: &'static str,
+}
+
+impl EmbeddedAssets {
+    /// Used by `include_assets!`; `files` must be sorted by path.
+    #[doc(hidden)]
+    pub const fn new(files: &'static [EmbeddedFile]) -> Self {
+        Self { files }
+    }
+
+    /// Look up a file by its path relative to the embedded root, e.g. "js/main.js".
+    pub fn get(&self, path: &str) -> Option<&'static EmbeddedFile> {
+        let files = self.files;
+        files
+            .binary_search_by(|f| f.path.cmp(path))
+            .ok()
+            .map(|i| &files[i])
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = &'static EmbeddedFile> {
+        self.files.iter()
+    }
+}
+
+impl EmbeddedFile {
+    #[doc(hidden)]
+    pub const fn new(
+        path: &'static str,
+        mime: &'static str,
+        data: &'static [u8],
+        gzip: Option<&'static [u8]>,
+        etag: &'static str,
+    ) -> Self {
+        Self { path, mime, data, gzip, etag }
+    }
+
+    pub fn path(&self) -> &'static str {
+        self.path
+    }
+
+    pub fn mime(&self) -> &'static str {
+        self.mime
+    }
+
+    pub fn data(&self) -> &'static [u8] {
+        self.data
+    }
+
+    /// Gzip encoded copy, present when compression paid off at build time.
+    pub fn gzip(&self) -> Option<&'static [u8]> {
+        self.gzip
+    }
+
+    /// Quoted content hash, used as the ETag of the identity encoding.
+    pub fn etag(&self) -> &'static str {
+        self.etag
+    }
+}
@@ -4,6 +4,10 @@ use std::fmt::{Display, Formatter};
 pub enum RuntimeError {
     AssetRootNotSet,
     AssetRootMissing(std::path::PathBuf),
+    ArchiveInvalid(std::path::PathBuf, String),
+    DevProxyInvalid(String),
+    HarInvalid(std::path::PathBuf, String),
+    HarUnrecorded(std::path::PathBuf, Vec<String>),
     CefInitializeFailed,
     CefNotInstalled,
 }
@@ -33,6 +37,50 @@ Ensure your frontend build output exists before launching the runtime.",
                 p.display()
             ),
 
+            RuntimeError::ArchiveInvalid(p, reason) => write!(
+                f,
+                "Asset archive could not be read:
+
+  {}
+  {}
+
+Rebuild it with `kurogane bundle --pack`.",
+                p.display(),
+                reason
+            ),
+
+            RuntimeError::DevProxyInvalid(reason) => write!(
+                f,
+                "Dev proxy target is not usable:
+
+  {}
+
+Use a plain http:// URL such as http://localhost:5173.",
+                reason
+            ),
+
+            RuntimeError::HarInvalid(p, reason) => write!(
+                f,
+                "HAR file could not be read or written:
+
+  {}
+  {}",
+                p.display(),
+                reason
+            ),
+
+            RuntimeError::HarUnrecorded(p, requests) => write!(
+                f,
+                "{} requests had no recorded response in {} and were blocked:
+
+  {}
+
+Record them again with KUROGANE_RECORD_HAR.",
+                requests.len(),
+                p.display(),
+                requests.join("\n  ")
+            ),
+
             RuntimeError::CefInitializeFailed => write!(
                 f,
                 "Chromium Embedded Framework failed to initialize.
@@ -0,0 +1,556 @@
+//! HAR recording and replay of the browser's network traffic.
+//!
+//! Recording copies every request and response seen by the resource request
+//! hooks in intercept.rs, app:// included, into a HAR 1.2 file. Entries
+//! are written as they complete, so a long session doesn't accumulate
+//! them in memory, and the file is closed when the runtime shuts down.
+//!
+//! Replay answers http(s) requests from such a file and blocks anything it
+//! doesn't contain, so end-to-end tests run without a network.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use cef::*;
+use serde_json::{json, Value};
+
+use crate::error::RuntimeError;
+use crate::intercept::{Action, InterceptedRequest};
+use crate::scheme::cache::civil_from_days;
+use crate::SchemeResponse;
+
+// Larger bodies are recorded up to this size and marked truncated
+const MAX_BODY: usize = 32 * 1024 * 1024;
+
+// Set by the response body, not by the recorded headers
+const SKIPPED_HEADERS: &[&str] = &["content-encoding", "content-length", "transfer-encoding", "content-type"];
+
+static RECORDER: OnceLock<Recorder> = OnceLock::new();
+static REPLAY: OnceLock<Replay> = OnceLock::new();
+
+struct Recorder {
+    path: PathBuf,
+    out: Mutex<Writer>,
+}
+
+/// The open HAR file, between the `entries` bracket and the closing one.
+struct Writer {
+    file: BufWriter<File>,
+    count: usize,
+    /// First write error; reported by finish().
+    error: Option<String>,
+}
+
+struct Replay {
+    path: PathBuf,
+    /// Recorded responses by method and URL, in the order they happened.
+    responses: HashMap<(String, String), Vec<Recorded>>,
+    /// How many responses of each key were served so far.
+    served: Mutex<HashMap<(String, String), usize>>,
+    /// Requests that had no recorded response.
+    missed: Mutex<Vec<String>>,
+}
+
+struct Recorded {
+    status: u16,
+    mime: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+/// Start recording to `path`, completed by finish().
+pub(crate) fn record(path: PathBuf) -> Result<(), RuntimeError> {
+    if RECORDER.get().is_some() {
+        eprintln!("[har] recording already configured, ignoring");
+        return Ok(());
+    }
+
+    let writer = Writer::create(&path).map_err(|e| RuntimeError::HarInvalid(path.clone(), e.to_string()))?;
+    let recorder = Recorder { path, out: Mutex::new(writer) };
+
+    if RECORDER.set(recorder).is_err() {
+        eprintln!("[har] recording already configured, ignoring");
+    }
+
+    Ok(())
+}
+
+/// Load `path` and answer http(s) requests from it.
+pub(crate) fn replay(path: PathBuf) -> Result<(), RuntimeError> {
+    let invalid = |e: String| RuntimeError::HarInvalid(path.clone(), e);
+
+    let text = std::fs::read_to_string(&path).map_err(|e| invalid(e.to_string()))?;
+    let har: Value = serde_json::from_str(&text).map_err(|e| invalid(e.to_string()))?;
+
+    let entries = har["log"]["entries"]
+        .as_array()
+        .ok_or_else(|| invalid("no log.entries array".into()))?;
+
+    let mut responses: HashMap<_, Vec<_>> = HashMap::new();
+
+    for entry in entries {
+        let (Some(method), Some(url)) = (entry["request"]["method"].as_str(), entry["request"]["url"].as_str()) else {
+            continue;
+        };
+
+        responses
+            .entry((method.to_string(), url.to_string()))
+            .or_default()
+            .push(Recorded::from_har(&entry["response"]));
+    }
+
+    let replay = Replay {
+        path,
+        responses,
+        served: Mutex::new(HashMap::new()),
+        missed: Mutex::new(Vec::new()),
+    };
+
+    if REPLAY.set(replay).is_err() {
+        eprintln!("[har] replay already configured, ignoring");
+    }
+
+    Ok(())
+}
+
+/// Whether requests are being recorded.
+pub(crate) fn recording() -> bool {
+    RECORDER.get().is_some()
+}
+
+/// Whether either mode needs the resource request hooks.
+pub(crate) fn enabled() -> bool {
+    recording() || REPLAY.get().is_some()
+}
+
+/// Whether `url` is answered from the replay file. The app's own schemes
+/// are always served live.
+pub(crate) fn replays(url: &str) -> bool {
+    REPLAY.get().is_some() && (url.starts_with("http://") || url.starts_with("https://"))
+}
+
+/// The next recorded response for a request, or Block if there is none.
+/// Repeated requests get the recorded responses in order, then the last one again.
+pub(crate) fn replayed(request: &InterceptedRequest) -> Action {
+    let Some(replay) = REPLAY.get() else {
+        return Action::Allow;
+    };
+
+    let key = (request.method().to_string(), request.url().to_string());
+
+    let Some(responses) = replay.responses.get(&key) else {
+        eprintln!("[har] no recorded response for {} {}; blocked", key.0, key.1);
+        replay.missed.lock().unwrap().push(format!("{} {}", key.0, key.1));
+        return Action::Block;
+    };
+
+    let mut served = replay.served.lock().unwrap();
+    let count = served.entry(key).or_default();
+    let recorded = &responses[(*count).min(responses.len() - 1)];
+    *count += 1;
+
+    // Status 0 means the request failed when it was recorded
+    if recorded.status == 0 {
+        return Action::Block;
+    }
+
+    let mut response = SchemeResponse::bytes(recorded.mime.clone(), recorded.body.clone()).status(recorded.status);
+
+    for (name, value) in &recorded.headers {
+        response = response.header(name.clone(), value.clone());
+    }
+
+    Action::Respond(response)
+}
+
+/// Write the recording and report replay misses. Called once the runtime
+/// has shut down.
+pub(crate) fn finish() -> Result<(), RuntimeError> {
+    if let Some(recorder) = RECORDER.get() {
+        let mut out = recorder.out.lock().unwrap();
+        let count = out.count;
+
+        out.close()
+            .map_err(|e| RuntimeError::HarInvalid(recorder.path.clone(), e))?;
+
+        eprintln!("[har] recorded {} requests to {}", count, recorder.path.display());
+    }
+
+    if let Some(replay) = REPLAY.get() {
+        let missed = std::mem::take(&mut *replay.missed.lock().unwrap());
+
+        if !missed.is_empty() {
+            return Err(RuntimeError::HarUnrecorded(replay.path.clone(), missed));
+        }
+    }
+
+    Ok(())
+}
+
+impl Recorded {
+    fn from_har(response: &Value) -> Self {
+        let content = &response["content"];
+        let text = content["text"].as_str().unwrap_or_default();
+
+        let body = match content["encoding"].as_str() {
+            Some("base64") => base64_decode(text).unwrap_or_default(),
+            _ => text.as_bytes().to_vec(),
+        };
+
+        let headers = response["headers"]
+            .as_array()
+            .map(|headers| {
+                headers
+                    .iter()
+                    .filter_map(|h| Some((h["name"].as_str()?.to_string(), h["value"].as_str()?.to_string())))
+                    .filter(|(name, _)| !SKIPPED_HEADERS.iter().any(|s| name.eq_ignore_ascii_case(s)))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self {
+            status: response["status"].as_u64().unwrap_or(0) as u16,
+            mime: content["mimeType"].as_str().unwrap_or(crate::mime::DEFAULT).to_string(),
+            headers,
+            body,
+        }
+    }
+}
+
+//
+// Recording
+//
+
+impl Writer {
+    /// Create `path` and write everything up to the first entry.
+    fn create(path: &Path) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+
+        let creator = json!({ "name": "kurogane", "version": env!("CARGO_PKG_VERSION") });
+        write!(file, "{{\"log\":{{\"version\":\"1.2\",\"creator\":{},\"entries\":[", creator)?;
+
+        Ok(Self { file, count: 0, error: None })
+    }
+
+    /// Append one entry. Errors are kept for close() rather than returned,
+    /// since they happen on a network thread with nobody to report to.
+    fn push(&mut self, entry: &Value) {
+        let separator: &[u8] = if self.count == 0 { b"\n" } else { b",\n" };
+
+        let written = self
+            .file
+            .write_all(separator)
+            .and_then(|_| serde_json::to_writer(&mut self.file, entry).map_err(io::Error::from));
+
+        match written {
+            Ok(()) => self.count += 1,
+            Err(e) if self.error.is_none() => {
+                eprintln!("[har] writing the recording failed: {}", e);
+                self.error = Some(e.to_string());
+            }
+            Err(_) => {}
+        }
+    }
+
+    /// Close the entries array and flush.
+    fn close(&mut self) -> Result<(), String> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+
+        self.file
+            .write_all(b"\n]}}\n")
+            .and_then(|_| self.file.flush())
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// A request that was sent and whose response is still arriving.
+pub(crate) struct Pending {
+    started: SystemTime,
+    clock: Instant,
+    request: Value,
+    body: Arc<Mutex<Capture>>,
+}
+
+#[derive(Default)]
+struct Capture {
+    data: Vec<u8>,
+    truncated: bool,
+}
+
+impl Pending {
+    pub fn new(request: &InterceptedRequest) -> Self {
+        let mime = request.header("Content-Type").unwrap_or(crate::mime::DEFAULT);
+
+        let mut har = json!({
+            "method": request.method(),
+            "url": request.url(),
+            "httpVersion": "HTTP/1.1",
+            "headers": headers_json(request.headers()),
+            "queryString": query_json(request.url()),
+            "cookies": [],
+            "headersSize": -1,
+            "bodySize": request.body().len(),
+        });
+
+        if !request.body().is_empty() {
+            har["postData"] = json!({
+                "mimeType": mime,
+                "text": String::from_utf8_lossy(request.body()),
+            });
+        }
+
+        Self {
+            started: SystemTime::now(),
+            clock: Instant::now(),
+            request: har,
+            body: Arc::new(Mutex::new(Capture::default())),
+        }
+    }
+
+    /// Filter that copies the response body as the page receives it.
+    pub fn filter(&self) -> ResponseFilter {
+        CaptureFilter::new(self.body.clone())
+    }
+
+    /// Add the finished exchange to the recording.
+    pub fn finish(self, response: &Response) {
+        let Some(recorder) = RECORDER.get() else {
+            return;
+        };
+
+        let status_text: CefString = (&response.status_text()).into();
+        let mime: CefString = (&response.mime_type()).into();
+
+        let mut map = CefStringMultimap::new();
+        response.header_map(Some(&mut map));
+        let headers: Vec<(String, String)> = map.into_iter().collect();
+
+        let capture = std::mem::take(&mut *self.body.lock().unwrap());
+        let size = capture.data.len();
+
+        let mut content = match String::from_utf8(capture.data) {
+            Ok(text) => json!({ "size": size, "mimeType": mime.to_string(), "text": text }),
+            Err(e) => json!({
+                "size": size,
+                "mimeType": mime.to_string(),
+                "text": base64_encode(e.as_bytes()),
+                "encoding": "base64",
+            }),
+        };
+
+        if capture.truncated {
+            content["comment"] = json!(format!("truncated to {} bytes", MAX_BODY));
+        }
+
+        let location = headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case("Location"))
+            .map_or("", |(_, v)| v.as_str());
+
+        let time = self.clock.elapsed().as_secs_f64() * 1000.0;
+
+        let entry = json!({
+            "startedDateTime": iso_8601(self.started),
+            "time": time,
+            "request": self.request,
+            "response": {
+                "status": response.status(),
+                "statusText": status_text.to_string(),
+                "httpVersion": "HTTP/1.1",
+                "headers": headers_json(&headers),
+                "cookies": [],
+                "content": content,
+                "redirectURL": location,
+                "headersSize": -1,
+                "bodySize": size,
+            },
+            "cache": {},
+            "timings": { "send": 0, "wait": time, "receive": 0 },
+        });
+
+        recorder.out.lock().unwrap().push(&entry);
+    }
+}
+
+wrap_response_filter! {
+    pub struct CaptureFilter {
+        body: Arc<Mutex<Capture>>,
+    }
+
+    impl ResponseFilter {
+        fn init_filter(&self) -> i32 {
+            1
+        }
+
+        fn filter(
+            &self,
+            data_in: *mut u8,
+            data_in_size: usize,
+            data_in_read: Option<&mut usize>,
+            data_out: *mut u8,
+            data_out_size: usize,
+            data_out_written: Option<&mut usize>,
+        ) -> ResponseFilterStatus {
+            // Null input: the body is complete
+            if data_in.is_null() {
+                if let Some(written) = data_out_written {
+                    *written = 0;
+                }
+                return ResponseFilterStatus::DONE;
+            }
+
+            // Pass through what fits; CEF calls again with the rest
+            let n = data_in_size.min(data_out_size);
+
+            // SAFETY: CEF provides data_in_size readable and data_out_size writable bytes
+            let input = unsafe { std::slice::from_raw_parts(data_in, n) };
+            unsafe { std::ptr::copy_nonoverlapping(data_in, data_out, n) };
+
+            let mut capture = self.body.lock().unwrap();
+            let room = MAX_BODY.saturating_sub(capture.data.len());
+            capture.truncated |= n > room;
+            capture.data.extend_from_slice(&input[..n.min(room)]);
+
+            if let Some(read) = data_in_read {
+                *read = n;
+            }
+            if let Some(written) = data_out_written {
+                *written = n;
+            }
+
+            ResponseFilterStatus::NEED_MORE_DATA
+        }
+    }
+}
+
+//
+// Helpers
+//
+
+fn headers_json(headers: &[(String, String)]) -> Value {
+    headers
+        .iter()
+        .map(|(name, value)| json!({ "name": name, "value": value }))
+        .collect()
+}
+
+/// Query parameters of `url` as HAR name/value pairs, still encoded.
+fn query_json(url: &str) -> Value {
+    let query = url.split('#').next().unwrap_or("").split_once('?').map_or("", |(_, q)| q);
+
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            json!({ "name": name, "value": value })
+        })
+        .collect()
+}
+
+/// `2026-01-02T03:04:05.678Z`
+fn iso_8601(time: SystemTime) -> String {
+    let since = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since.as_secs();
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        secs / 3600 % 24,
+        secs / 60 % 60,
+        secs % 60,
+        since.subsec_millis()
+    )
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+
+    for chunk in data.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * i)) as usize & 63] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+
+    out
+}
+
+fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(text.len() / 4 * 3);
+    let mut bits = 0u32;
+    let mut count = 0;
+
+    for c in text.bytes().filter(|c| !c.is_ascii_whitespace() && *c != b'=') {
+        let value = BASE64.iter().position(|b| *b == c)? as u32;
+        bits = ((bits << 6) | value) & 0xFF_FFFF;
+        count += 6;
+
+        if count >= 8 {
+            count -= 8;
+            out.push((bits >> count) as u8);
+        }
+    }
+
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_matches_rfc_4648_vectors() {
+        for (plain, encoded) in [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ] {
+            assert_eq!(base64_encode(plain.as_bytes()), encoded);
+            assert_eq!(base64_decode(encoded).unwrap(), plain.as_bytes());
+        }
+    }
+
+    #[test]
+    fn base64_round_trips_every_byte_and_length() {
+        let data: Vec<u8> = (0..=255).chain((0..=255).rev()).collect();
+
+        for len in 0..data.len() {
+            let encoded = base64_encode(&data[..len]);
+            assert_eq!(encoded.len() % 4, 0);
+            assert_eq!(base64_decode(&encoded).unwrap(), &data[..len], "length {}", len);
+        }
+    }
+
+    #[test]
+    fn base64_decode_tolerates_whitespace_and_missing_padding() {
+        assert_eq!(base64_decode("Zm9v\r\nYmFy").unwrap(), b"foobar");
+        assert_eq!(base64_decode("Zg").unwrap(), b"f");
+        assert_eq!(base64_decode("Zm8").unwrap(), b"fo");
+        assert_eq!(base64_decode("/+/+").unwrap(), [0xff, 0xef, 0xfe]);
+
+        assert!(base64_decode("Zm9v!").is_none());
+        assert!(base64_decode("Zm9v-_").is_none());
+    }
+}
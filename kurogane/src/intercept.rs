@@ -0,0 +1,259 @@
+//! Interception of requests made by the page, registered with App::on_request.
+//!
+//! Built on CEF's ResourceRequestHandler, so it sees every request a browser
+//! makes: http(s), app:// and other custom schemes, navigations and
+//! subresources alike. Interceptors run on CEF's IO thread; keep them fast.
+//! HAR recording and replay (har.rs) hook in here too.
+
+use std::sync::{Arc, Mutex, OnceLock};
+
+use cef::*;
+
+use crate::debug;
+use crate::har;
+use crate::scheme::{self, glob::Glob};
+use crate::SchemeResponse;
+
+pub(crate) type Interceptor = Box<dyn Fn(&InterceptedRequest) -> Action + Send + Sync + 'static>;
+
+static INTERCEPTORS: OnceLock<Vec<(Glob, Interceptor)>> = OnceLock::new();
+
+/// A request about to be sent, as seen by an App::on_request interceptor.
+pub struct InterceptedRequest {
+    method: String,
+    url: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    navigation: bool,
+}
+
+/// What to do with an intercepted request.
+pub enum Action {
+    /// Send it unchanged.
+    Allow,
+    /// Cancel it; the page sees a network error.
+    Block,
+    /// Load this URL instead.
+    Redirect(String),
+    /// Send it with these request headers added or replaced.
+    SetHeaders(Vec<(String, String)>),
+    /// Don't send it; answer with this response instead.
+    Respond(SchemeResponse),
+}
+
+impl InterceptedRequest {
+    /// HTTP method, e.g. "GET" or "POST".
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+
+    /// First value of a header, matched case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Upload body; file elements are skipped.
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Whether this loads a document into a frame rather than a subresource.
+    pub fn is_navigation(&self) -> bool {
+        self.navigation
+    }
+}
+
+/// Install the interceptors. Only the first call has an effect.
+pub(crate) fn set_interceptors(interceptors: Vec<(Glob, Interceptor)>) {
+    if INTERCEPTORS.set(interceptors).is_err() {
+        eprintln!("[intercept] interceptors already configured, ignoring");
+    }
+}
+
+/// Whether any interceptor or HAR mode is installed; requests skip the
+/// hooks otherwise.
+pub(crate) fn active() -> bool {
+    INTERCEPTORS.get().is_some_and(|i| !i.is_empty()) || har::enabled()
+}
+
+/// Ask the interceptors matching the request's URL, in the order added.
+/// The first answer other than Allow wins; in replay mode the recording
+/// answers what they allow.
+fn decide(request: &Request, navigation: bool) -> Action {
+    let url: CefString = (&request.url()).into();
+    let url = url.to_string();
+
+    let interceptors = INTERCEPTORS.get().map_or(&[][..], Vec::as_slice);
+    let mut matching = interceptors.iter().filter(|(glob, _)| glob.matches(&url)).peekable();
+    let replayed = har::replays(&url);
+
+    // Copy the request only if someone will look at it
+    if matching.peek().is_none() && !replayed {
+        return Action::Allow;
+    }
+
+    let intercepted = copy_request(request, navigation);
+
+    for (_, interceptor) in matching {
+        let action = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| interceptor(&intercepted)))
+            .unwrap_or_else(|_| {
+                eprintln!("[intercept] interceptor for {} panicked; blocking", intercepted.url);
+                Action::Block
+            });
+
+        if !matches!(action, Action::Allow) {
+            return action;
+        }
+    }
+
+    if replayed {
+        return har::replayed(&intercepted);
+    }
+
+    Action::Allow
+}
+
+fn copy_request(request: &Request, navigation: bool) -> InterceptedRequest {
+    let url: CefString = (&request.url()).into();
+    let method: CefString = (&request.method()).into();
+
+    let mut map = CefStringMultimap::new();
+    request.header_map(Some(&mut map));
+
+    InterceptedRequest {
+        method: method.to_string(),
+        url: url.to_string(),
+        headers: map.into_iter().collect(),
+        body: scheme::request_body(request),
+        navigation,
+    }
+}
+
+//
+// RequestHandler
+//
+
+wrap_request_handler! {
+    pub struct InterceptRequestHandler;
+
+    impl RequestHandler {
+        fn resource_request_handler(
+            &self,
+            _browser: Option<&mut Browser>,
+            _frame: Option<&mut Frame>,
+            _request: Option<&mut Request>,
+            is_navigation: i32,
+            _is_download: i32,
+            _request_initiator: Option<&CefString>,
+            _disable_default_handling: Option<&mut i32>,
+        ) -> Option<ResourceRequestHandler> {
+            // One per request, so a mocked response and the HAR entry can be
+            // handed from on_before_resource_load to the later hooks
+            Some(InterceptResourceRequestHandler::new(
+                is_navigation != 0,
+                Arc::new(Mutex::new(None)),
+                Arc::new(Mutex::new(None)),
+            ))
+        }
+    }
+}
+
+wrap_resource_request_handler! {
+    pub struct InterceptResourceRequestHandler {
+        navigation: bool,
+        mock: Arc<Mutex<Option<SchemeResponse>>>,
+        recorded: Arc<Mutex<Option<har::Pending>>>,
+    }
+
+    impl ResourceRequestHandler {
+        fn on_before_resource_load(
+            &self,
+            _browser: Option<&mut Browser>,
+            _frame: Option<&mut Frame>,
+            request: Option<&mut Request>,
+            _callback: Option<&mut Callback>,
+        ) -> ReturnValue {
+            let Some(request) = request else {
+                return ReturnValue::CONTINUE;
+            };
+
+            match decide(request, self.navigation) {
+                Action::Allow => {}
+                Action::Block => {
+                    let url: CefString = (&request.url()).into();
+                    debug!("[intercept] blocked {}", url.to_string());
+                    return ReturnValue::CANCEL;
+                }
+                // Changing the URL here makes CEF redirect the request
+                Action::Redirect(url) => {
+                    request.set_url(Some(&CefString::from(url.as_str())));
+                }
+                Action::SetHeaders(headers) => {
+                    for (name, value) in headers {
+                        request.set_header_by_name(
+                            Some(&CefString::from(name.as_str())),
+                            Some(&CefString::from(value.as_str())),
+                            1,
+                        );
+                    }
+                }
+                Action::Respond(response) => {
+                    *self.mock.lock().unwrap() = Some(response);
+                }
+            }
+
+            // After the action, so the recording shows what was actually sent
+            if har::recording() {
+                let sent = copy_request(request, self.navigation);
+                *self.recorded.lock().unwrap() = Some(har::Pending::new(&sent));
+            }
+
+            ReturnValue::CONTINUE
+        }
+
+        fn resource_handler(
+            &self,
+            _browser: Option<&mut Browser>,
+            _frame: Option<&mut Frame>,
+            _request: Option<&mut Request>,
+        ) -> Option<ResourceHandler> {
+            // None lets the request go out as usual
+            self.mock.lock().unwrap().take().map(scheme::canned)
+        }
+
+        fn resource_response_filter(
+            &self,
+            _browser: Option<&mut Browser>,
+            _frame: Option<&mut Frame>,
+            _request: Option<&mut Request>,
+            _response: Option<&mut Response>,
+        ) -> Option<ResponseFilter> {
+            self.recorded.lock().unwrap().as_ref().map(har::Pending::filter)
+        }
+
+        fn on_resource_load_complete(
+            &self,
+            _browser: Option<&mut Browser>,
+            _frame: Option<&mut Frame>,
+            _request: Option<&mut Request>,
+            response: Option<&mut Response>,
+            _status: UrlrequestStatus,
+            _received_content_length: i64,
+        ) {
+            if let (Some(pending), Some(response)) = (self.recorded.lock().unwrap().take(), response) {
+                pending.finish(response);
+            }
+        }
+    }
+}
@@ -2,24 +2,40 @@
 //! Commands may be registered before CEF starts.
 //! They are buffered and installed once the browser process initializes.
 //! Exposes JSON API while transport remains string based.
+//! Wire format is defined in ipc_protocol.
 
 use cef::*;
 use std::sync::{Arc, Mutex, OnceLock};
 use std::collections::HashMap;
+use std::time::{Duration, Instant};
 use serde_json::Value;
 
-use crate::ipc_shm::{SharedBuffer, SHM_THRESHOLD};
+use crate::ipc_protocol::{
+    Blob, IpcMessage, Text, TypeTag,
+    PROTOCOL_VERSION, RUNTIME_VERSION,
+    check_peer, is_ipc,
+};
+use crate::ipc_limits::limits;
+use crate::ipc_shm::{Payload, SharedBuffer, SHM_THRESHOLD, own_prefix};
+use crate::metrics::{self, Call};
+use crate::typed_buffer::{DType, TypedBuffer};
 use crate::debug;
 
 pub type IpcResult = Result<String, String>;
 pub type IpcHandler = Box<dyn Fn(&str) -> IpcResult + Send + Sync>;
 
 pub type BinaryHandler =
-    Box<dyn Fn(&[u8]) -> Result<Vec<u8>, String> + Send + Sync>;
+    Box<dyn Fn(&[u8]) -> Result<TypedBuffer, String> + Send + Sync>;
+
+/// JSON metadata plus a list of binary attachments, in both directions.
+pub type AttachmentResult = Result<(String, Vec<Vec<u8>>), String>;
+pub type AttachmentHandler =
+    Box<dyn Fn(&str, Vec<Vec<u8>>) -> AttachmentResult + Send + Sync>;
 
 pub struct IpcDispatcher {
     handlers: HashMap<String, IpcHandler>,
     binary_handlers: HashMap<String, BinaryHandler>,
+    attachment_handlers: HashMap<String, AttachmentHandler>,
 }
 
 struct PendingCall {
@@ -27,11 +43,31 @@ struct PendingCall {
     frame_id: String,
 }
 
+/// Frame a message came from. Call ids are chosen by each renderer, so
+/// they are only unique within one frame.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct FrameKey {
+    browser: i32,
+    frame: String,
+}
+
+/// Response SHM waiting for the renderer's SHM_FREE.
+struct HeldResponse {
+    buffers: Vec<SharedBuffer>,
+    since: Instant,
+}
+
+// Backstop for SHM_FREE messages that never arrive (renderer crash, lost
+// message). Dropping our side only releases the quota: a renderer that
+// already mapped the region keeps its own mapping.
+const RESPONSE_SHM_TIMEOUT: Duration = Duration::from_secs(120);
+
 impl IpcDispatcher {
     fn new() -> Self {
         Self {
             handlers: HashMap::new(),
             binary_handlers: HashMap::new(),
+            attachment_handlers: HashMap::new(),
         }
     }
 
@@ -43,18 +79,76 @@ impl IpcDispatcher {
         self.binary_handlers.insert(command.into(), handler);
     }
 
+    pub fn register_attachments(&mut self, command: impl Into<String>, handler: AttachmentHandler) {
+        self.attachment_handlers.insert(command.into(), handler);
+    }
+
     fn dispatch(&self, command: &str, payload: &str) -> IpcResult {
-        match self.handlers.get(command) {
-            Some(h) => h(payload),
-            None => Err(format!("[IPC] Unknown command '{}'", command)),
-        }
+        let response = match self.handlers.get(command) {
+            Some(h) => h(payload)?,
+            None => return Err(format!("[IPC] Unknown command '{}'", command)),
+        };
+
+        limits().check_response(command, response.len())?;
+        Ok(response)
     }
 
-    fn dispatch_binary(&self, command: &str, payload: &[u8]) -> Result<Vec<u8>, String> {
-        match self.binary_handlers.get(command) {
-            Some(h) => h(payload),
-            None => Err(format!("Unknown binary command '{}'", command)),
-        }
+    /// Dispatch every call of a batch under a single lock acquisition.
+    /// A panicking handler only fails its own entry.
+    /// Each call is recorded in metrics; `shm` tells whether the batch arrived via SHM.
+    fn dispatch_batch(&self, calls: &[(String, String)], shm: bool) -> Vec<IpcResult> {
+        calls
+            .iter()
+            .map(|(command, payload)| {
+                let started = Instant::now();
+
+                let result = limits()
+                    .check_request(command, payload.len())
+                    .and_then(|_| {
+                        std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                            self.dispatch(command, payload)
+                        }))
+                        .unwrap_or_else(|_| Err("IPC handler panicked".to_string()))
+                    });
+
+                record_call(
+                    command,
+                    shm,
+                    payload.len(),
+                    result.as_ref().ok().map(String::len),
+                    started,
+                );
+
+                result
+            })
+            .collect()
+    }
+
+    fn dispatch_binary(&self, command: &str, payload: &[u8]) -> Result<TypedBuffer, String> {
+        let response = match self.binary_handlers.get(command) {
+            Some(h) => h(payload)?,
+            None => return Err(format!("Unknown binary command '{}'", command)),
+        };
+
+        limits().check_response(command, response.as_bytes().len())?;
+        Ok(response)
+    }
+
+    fn dispatch_attachments(
+        &self,
+        command: &str,
+        meta: &str,
+        attachments: Vec<Vec<u8>>,
+    ) -> AttachmentResult {
+        let (meta, attachments) = match self.attachment_handlers.get(command) {
+            Some(h) => h(meta, attachments)?,
+            None => return Err(format!("Unknown attachment command '{}'", command)),
+        };
+
+        let size = meta.len() + attachments.iter().map(Vec::len).sum::<usize>();
+        limits().check_response(command, size)?;
+
+        Ok((meta, attachments))
     }
 }
 
@@ -71,12 +165,20 @@ static PENDING_COMMANDS: OnceLock<Mutex<Vec<(String, IpcHandler)>>> = OnceLock::
 // Binary commands also need a pending buffer
 static PENDING_BINARY_COMMANDS: OnceLock<Mutex<Vec<(String, BinaryHandler)>>> = OnceLock::new();
 
-static PENDING_CALLS: OnceLock<Mutex<HashMap<u32, PendingCall>>> = OnceLock::new();
+static PENDING_ATTACHMENT_COMMANDS: OnceLock<Mutex<Vec<(String, AttachmentHandler)>>> = OnceLock::new();
+
+static PENDING_CALLS: OnceLock<Mutex<HashMap<(FrameKey, u32), PendingCall>>> = OnceLock::new();
+
+// SHM prefix each renderer announced in its handshake, per (browser, frame).
+// Only regions carrying it are mapped for calls from that frame.
+static PEER_SHM_PREFIXES: OnceLock<Mutex<HashMap<FrameKey, String>>> = OnceLock::new();
 
-// Keep SHM alive until the renderer signals it has finished reading (msg_type 5)
-static RESPONSE_SHM_STORE: OnceLock<Mutex<HashMap<u32, SharedBuffer>>> = OnceLock::new();
+// Keep SHM alive until the renderer signals it has finished reading (msg_type 5).
+// A response may carry several SHM regions (attachments), all released together.
+// Entries of a frame are dropped when it closes or starts a new JS context.
+static RESPONSE_SHM_STORE: OnceLock<Mutex<HashMap<(FrameKey, u32), HeldResponse>>> = OnceLock::new();
 
-fn pending_calls() -> &'static Mutex<HashMap<u32, PendingCall>> {
+fn pending_calls() -> &'static Mutex<HashMap<(FrameKey, u32), PendingCall>> {
     PENDING_CALLS.get_or_init(|| Mutex::new(HashMap::new()))
 }
 
@@ -88,12 +190,75 @@ fn pending_binary_commands() -> &'static Mutex<Vec<(String, BinaryHandler)>> {
     PENDING_BINARY_COMMANDS.get_or_init(|| Mutex::new(Vec::new()))
 }
 
-fn response_shm_store() -> &'static Mutex<HashMap<u32, SharedBuffer>> {
+fn pending_attachment_commands() -> &'static Mutex<Vec<(String, AttachmentHandler)>> {
+    PENDING_ATTACHMENT_COMMANDS.get_or_init(|| Mutex::new(Vec::new()))
+}
+
+fn response_shm_store() -> &'static Mutex<HashMap<(FrameKey, u32), HeldResponse>> {
     RESPONSE_SHM_STORE.get_or_init(|| Mutex::new(HashMap::new()))
 }
 
+fn peer_shm_prefixes() -> &'static Mutex<HashMap<FrameKey, String>> {
+    PEER_SHM_PREFIXES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn frame_key(browser: &Browser, frame: &Frame) -> FrameKey {
+    let s: CefString = (&frame.identifier()).into();
+    FrameKey { browser: browser.identifier(), frame: s.to_string() }
+}
+
+/// Keep response SHM alive until the renderer sends SHM_FREE for `id`.
+fn hold_response(key: &FrameKey, id: u32, buffers: Vec<SharedBuffer>) {
+    response_shm_store()
+        .lock()
+        .unwrap()
+        .insert((key.clone(), id), HeldResponse { buffers, since: Instant::now() });
+}
+
+/// Drop response SHM whose SHM_FREE is overdue.
+fn expire_held_responses() {
+    let mut store = response_shm_store().lock().unwrap();
+    let before = store.len();
+
+    store.retain(|_, held| held.since.elapsed() < RESPONSE_SHM_TIMEOUT);
+
+    if store.len() < before {
+        eprintln!("[IPC] released {} SHM responses never freed by the renderer", before - store.len());
+    }
+}
+
+/// Forget everything held for `frame`: its JS context is gone.
+fn release_frame_state(key: &FrameKey) {
+    let mut store = response_shm_store().lock().unwrap();
+    let before = store.len();
+    store.retain(|(k, _), _| k != key);
+
+    let released = before - store.len();
+    if released > 0 {
+        debug!("[IPC] released {} SHM responses of frame {}", released, key.frame);
+    }
+
+    pending_calls().lock().unwrap().retain(|(k, _), _| k != key);
+}
+
+/// Called when a frame is detached from its browser.
+pub(crate) fn release_frame(browser: &Browser, frame: &Frame) {
+    let key = frame_key(browser, frame);
+    release_frame_state(&key);
+    peer_shm_prefixes().lock().unwrap().remove(&key);
+}
+
+/// Called when a browser is about to close.
+pub(crate) fn release_browser(browser: &Browser) {
+    let id = browser.identifier();
+
+    response_shm_store().lock().unwrap().retain(|(k, _), _| k.browser != id);
+    pending_calls().lock().unwrap().retain(|(k, _), _| k.browser != id);
+    peer_shm_prefixes().lock().unwrap().retain(|k, _| k.browser != id);
+}
+
 /// Dispatcher init: Called by runtime when browser process initializes.
-/// Drains both JSON and binary pending command queues.
+/// Drains the JSON, binary and attachment pending command queues.
 pub fn init_dispatcher() -> Arc<Mutex<IpcDispatcher>> {
     let dispatcher = DISPATCHER
         .get_or_init(|| Arc::new(Mutex::new(IpcDispatcher::new())))
@@ -102,6 +267,7 @@ pub fn init_dispatcher() -> Arc<Mutex<IpcDispatcher>> {
     {
         let mut pending = pending_commands().lock().unwrap();
         let mut pending_bin = pending_binary_commands().lock().unwrap();
+        let mut pending_att = pending_attachment_commands().lock().unwrap();
         let mut disp = dispatcher.lock().unwrap();
 
         for (cmd, handler) in pending.drain(..) {
@@ -110,6 +276,9 @@ pub fn init_dispatcher() -> Arc<Mutex<IpcDispatcher>> {
         for (cmd, handler) in pending_bin.drain(..) {
             disp.register_binary(cmd, handler);
         }
+        for (cmd, handler) in pending_att.drain(..) {
+            disp.register_attachments(cmd, handler);
+        }
     }
 
     dispatcher
@@ -150,11 +319,14 @@ where
 // Binary API
 //
 /// Register a binary command. Safe to call before runtime boot.
-pub fn register_binary_command<F>(command: impl Into<String>, handler: F)
+///
+/// Handlers return raw bytes (`Vec<u8>`) or a TypedBuffer carrying dtype and shape.
+pub fn register_binary_command<F, R>(command: impl Into<String>, handler: F)
 where
-    F: Fn(&[u8]) -> Result<Vec<u8>, String> + Send + Sync + 'static,
+    F: Fn(&[u8]) -> Result<R, String> + Send + Sync + 'static,
+    R: Into<TypedBuffer>,
 {
-    let wrapped: BinaryHandler = Box::new(handler);
+    let wrapped: BinaryHandler = Box::new(move |data: &[u8]| handler(data).map(Into::into));
 
     if let Some(dispatcher) = DISPATCHER.get() {
         dispatcher.lock().unwrap().register_binary(command.into(), wrapped);
@@ -165,20 +337,27 @@ where
 }
 
 //
-// Message helpers
+// Attachment API
 //
+/// Register a command taking and returning JSON metadata plus binary attachments.
+/// Safe to call before runtime boot.
+pub fn register_attachment_command<F>(command: impl Into<String>, handler: F)
+where
+    F: Fn(Value, Vec<Vec<u8>>) -> Result<(Value, Vec<Vec<u8>>), String> + Send + Sync + 'static,
+{
+    let wrapped: AttachmentHandler = Box::new(move |meta: &str, attachments| {
+        let input: Value =
+            serde_json::from_str(meta).unwrap_or(Value::String(meta.to_string()));
 
-fn list_get_int(args: &ListValue, idx: usize) -> i32 {
-    // binding exposes .int(index)
-    args.int(idx)
-}
+        handler(input, attachments)
+            .map(|(v, out)| (serde_json::to_string(&v).unwrap(), out))
+    });
 
-fn list_get_string(args: &ListValue, idx: usize) -> String {
-    // binding exposes .string(index) -> CefStringUserfree
-    let userfree = args.string(idx);
-    // Convert to CefString (borrow conversion) then to Rust String
-    let cef: CefString = (&userfree).into();
-    cef.to_string()
+    if let Some(dispatcher) = DISPATCHER.get() {
+        dispatcher.lock().unwrap().register_attachments(command.into(), wrapped);
+    } else {
+        pending_attachment_commands().lock().unwrap().push((command.into(), wrapped));
+    }
 }
 
 //
@@ -186,109 +365,272 @@ fn list_get_string(args: &ListValue, idx: usize) -> String {
 //
 
 pub fn handle_ipc_message(
-    _browser: &mut Browser,
+    browser: &mut Browser,
     frame: &mut Frame,
     message: &mut ProcessMessage,
 ) -> bool {
-    let name: CefString = (&message.name()).into();
-    if name.to_string() != "ipc" {
+    if !is_ipc(message) {
         return false;
     }
 
-    let args = match message.argument_list() {
-        Some(a) => a,
-        None => return false,
+    let msg = match IpcMessage::decode(message) {
+        Ok(msg) => msg,
+        Err(e) => {
+            eprintln!("[IPC ERROR] {}", e);
+
+            // Fail the caller instead of leaving its promise pending forever
+            if let Some(id) = e.id() {
+                IpcMessage::Reject { id, error: e.to_string() }.send(frame, ProcessId::RENDERER);
+            }
+            return true;
+        }
     };
 
-    // Message type: 0 = invoke, 1 = resolve (browser shouldn't receive), 2 = reject
-    let msg_type = list_get_int(&args, 0);
+    expire_held_responses();
 
-    match msg_type {
+    let key = frame_key(browser, frame);
+    let peer = peer_shm_prefixes().lock().unwrap().get(&key).cloned();
+    let peer = peer.as_deref();
 
-        // JSON invoke
-        0 => {
-            let id = list_get_int(&args, 1) as u32;
-            let command = list_get_string(&args, 2);
-            let payload = list_get_string(&args, 3);
+    match msg {
 
+        // JSON invoke
+        IpcMessage::Invoke { id, command, payload } => {
             debug!("[Browser] IPC invoke: '{}' (id={})", command, id);
 
-            let dispatcher = get_dispatcher();
-            let result = std::panic::catch_unwind(|| {
-                dispatcher.lock().unwrap().dispatch(&command, &payload)
-            })
-            .unwrap_or_else(|_| Err("IPC handler panicked".to_string()));
-
-            let frame_id = {
-                let s: CefString = (&frame.identifier()).into();
-                s.to_string()
+            let started = Instant::now();
+            let request_bytes = payload.size();
+            let request_shm = matches!(payload, Text::Shared { .. });
+
+            // Size is declared up front, so oversized payloads are refused before mapping
+            // SHM payloads are copied out; renderer keeps its own alive until our response arrives
+            let result = match limits()
+                .check_request(&command, payload.size())
+                .and_then(|_| payload.open(peer).map_err(|e| format!("[IPC] shm transport error: {}", e)))
+            {
+                Ok(payload) => {
+                    let dispatcher = get_dispatcher();
+                    std::panic::catch_unwind(|| {
+                        dispatcher.lock().unwrap().dispatch(&command, &payload)
+                    })
+                    .unwrap_or_else(|_| Err("IPC handler panicked".to_string()))
+                }
+                Err(e) => Err(e),
             };
 
-            pending_calls().lock().unwrap().insert(
-                id,
-                PendingCall { frame: frame.clone(), frame_id },
+            let response_bytes = result.as_ref().ok().map(String::len);
+            record_call(
+                &command,
+                request_shm || response_bytes.is_some_and(|s| s >= SHM_THRESHOLD),
+                request_bytes,
+                response_bytes,
+                started,
             );
 
-            send_response(id, result);
-            true
+            track_call(&key, id, frame);
+            send_response(&key, id, result);
+        }
+
+        // Batched JSON invoke: one message carrying [[command, payload], ...]
+        IpcMessage::InvokeBatch { id, calls } => {
+            let batch_shm = matches!(calls, Text::Shared { .. });
+
+            let batch = limits()
+                .check_batch(calls.size())
+                .and_then(|_| calls.open(peer).map_err(|e| format!("[IPC] shm transport error: {}", e)))
+                .and_then(|b| {
+                    serde_json::from_str::<Vec<(String, String)>>(&b)
+                        .map_err(|e| format!("[IPC] Malformed batch: {}", e))
+                });
+
+            let result = match batch {
+                Ok(calls) => {
+                    debug!("[Browser] IPC batch: {} calls (id={})", calls.len(), id);
+
+                    let results = get_dispatcher().lock().unwrap().dispatch_batch(&calls, batch_shm);
+                    Ok(encode_batch_results(results))
+                }
+                Err(e) => Err(e),
+            };
+
+            track_call(&key, id, frame);
+            send_response(&key, id, result);
         }
 
         // Binary invoke
-        3 => {
-            let id = list_get_int(&args, 1) as u32;
-            let command = list_get_string(&args, 2);
-
-            let data: Vec<u8> = if let Some(binary) = args.binary(3) {
-                let size = binary.size();
-                let mut buf = vec![0u8; size];
-
-                let written = binary.data(Some(&mut buf), 0);
-                buf.truncate(written);
-                debug!("[Browser] inline binary: {} bytes", written);
-                buf
-            } else {
-                // Large payload via SHM; open before the renderer drops it
-                let name = list_get_string(&args, 3);
-                let size = list_get_int(&args, 4) as usize;
-                let shm = SharedBuffer::open(&name, size)
-                    .unwrap_or_else(|e| panic!("[IPC] {}", e));
-                shm.as_slice().to_vec()
-                // shm unmapped here; renderer keeps its own alive until our response arrives
+        IpcMessage::InvokeBinary { id, command, data } => {
+            let started = Instant::now();
+            let request_bytes = data.size();
+            let request_shm = matches!(data, Blob::Shared { .. });
+
+            // Large payloads arrive via SHM; open before the renderer drops it
+            let result = match limits()
+                .check_request(&command, data.size())
+                .and_then(|_| data.open(peer).map_err(|e| format!("[IPC] shm transport error: {}", e)))
+            {
+                Ok(data) => {
+                    debug!(
+                        "[Browser] binary invoke: '{}' (id={}, {} bytes, shm={})",
+                        command, id, data.as_slice().len(), data.is_shared()
+                    );
+
+                    let dispatcher = get_dispatcher();
+                    std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                        dispatcher.lock().unwrap().dispatch_binary(&command, data.as_slice())
+                    }))
+                    .unwrap_or_else(|_| Err("Binary IPC handler panicked".to_string()))
+                    // shm unmapped here; renderer keeps its own alive until our response arrives
+                }
+                Err(e) => Err(e),
             };
 
-            debug!("[Browser] binary invoke: '{}' (id={}, {} bytes)", command, id, data.len());
+            let response_bytes = result.as_ref().ok().map(|typed| typed.as_bytes().len());
+            record_call(
+                &command,
+                request_shm || response_bytes.is_some_and(|s| s >= SHM_THRESHOLD),
+                request_bytes,
+                response_bytes,
+                started,
+            );
 
-            let dispatcher = get_dispatcher();
+            send_binary_response(&key, id, result, frame);
+        }
 
-            let result = std::panic::catch_unwind(|| {
-                dispatcher.lock().unwrap().dispatch_binary(&command, &data)
-            })
-            .unwrap_or_else(|_| Err("Binary IPC handler panicked".to_string()));
+        // Invoke with attachments
+        IpcMessage::InvokeAttachments { id, command, meta, attachments } => {
+            // Attachments are copied out; SHM is unmapped before dispatch,
+            // renderer keeps its own alive until our response arrives
+            let started = Instant::now();
+            let declared = meta.len() + attachments.iter().map(Blob::size).sum::<usize>();
+            let request_shm = attachments.iter().any(|b| matches!(b, Blob::Shared { .. }));
+
+            let attachments = limits().check_request(&command, declared).and_then(|_| {
+                attachments
+                    .into_iter()
+                    .map(|blob| blob.open(peer).map(Payload::into_vec))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| format!("[IPC] shm transport error: {}", e))
+            });
+
+            let result = match attachments {
+                Ok(attachments) => {
+                    debug!(
+                        "[Browser] attachment invoke: '{}' (id={}, {} attachments)",
+                        command, id, attachments.len()
+                    );
+
+                    let dispatcher = get_dispatcher();
+                    std::panic::catch_unwind(|| {
+                        dispatcher.lock().unwrap().dispatch_attachments(&command, &meta, attachments)
+                    })
+                    .unwrap_or_else(|_| Err("Attachment IPC handler panicked".to_string()))
+                }
+                Err(e) => Err(e),
+            };
 
-            send_binary_response(id, result, frame);
-            true
+            let (response_bytes, response_shm) = match &result {
+                Ok((meta, buffers)) => (
+                    Some(meta.len() + buffers.iter().map(Vec::len).sum::<usize>()),
+                    buffers.iter().any(|b| b.len() >= SHM_THRESHOLD),
+                ),
+                Err(_) => (None, false),
+            };
+            record_call(&command, request_shm || response_shm, declared, response_bytes, started);
+
+            send_attachment_response(&key, id, result, frame);
         }
 
-        // SHM_FREE: renderer has finished reading a large binary response
-        5 => {
-            let id = list_get_int(&args, 1) as u32;
+        // SHM_FREE: renderer has finished reading a large response
+        IpcMessage::ShmFree { id } => {
             debug!("[Browser] SHM_FREE for id={}", id);
-            response_shm_store().lock().unwrap().remove(&id);
-            true
+            response_shm_store().lock().unwrap().remove(&(key, id));
+        }
+
+        // Handshake: renderer announces its protocol and SHM prefix once per JS context
+        IpcMessage::Hello { protocol, version, shm_prefix } => {
+            match check_peer(protocol, &version) {
+                Some(mismatch) => eprintln!("[IPC ERROR] renderer: {}", mismatch),
+                None => debug!("[Browser] IPC handshake ok (protocol v{}, renderer {})", protocol, version),
+            }
+
+            // A new context cannot free the previous one's responses
+            release_frame_state(&key);
+            peer_shm_prefixes().lock().unwrap().insert(key, shm_prefix);
+
+            IpcMessage::HelloAck {
+                protocol: PROTOCOL_VERSION,
+                version: RUNTIME_VERSION.to_string(),
+                shm_prefix: own_prefix().to_string(),
+            }
+            .send(frame, ProcessId::RENDERER);
         }
 
-        _ => false,
+        other => {
+            eprintln!("[IPC ERROR] unexpected {:?} message from renderer", other.message_type());
+        }
     }
+
+    true
+}
+
+/// Record a dispatched call; `response_bytes` is None for failed calls.
+fn record_call(
+    command: &str,
+    shm: bool,
+    request_bytes: usize,
+    response_bytes: Option<usize>,
+    started: Instant,
+) {
+    metrics::record(Call {
+        command,
+        shm,
+        request_bytes,
+        response_bytes,
+        elapsed: started.elapsed(),
+    });
+}
+
+/// Remember the calling frame so send_response can drop stale replies.
+fn track_call(key: &FrameKey, id: u32, frame: &Frame) {
+    let frame_id = {
+        let s: CefString = (&frame.identifier()).into();
+        s.to_string()
+    };
+
+    pending_calls().lock().unwrap().insert(
+        (key.clone(), id),
+        PendingCall { frame: frame.clone(), frame_id },
+    );
 }
 
 //
 // JSON response
 //
 
-fn send_response(id: u32, result: IpcResult) {
+/// Encode per-call batch results in the shape of Promise.allSettled().
+fn encode_batch_results(results: Vec<IpcResult>) -> String {
+    let entries: Vec<Value> = results
+        .into_iter()
+        .map(|r| match r {
+            Ok(payload) => serde_json::json!({
+                "status": "fulfilled",
+                "value": serde_json::from_str::<Value>(&payload)
+                    .unwrap_or(Value::String(payload)),
+            }),
+            Err(err) => serde_json::json!({
+                "status": "rejected",
+                "reason": err,
+            }),
+        })
+        .collect();
+
+    Value::Array(entries).to_string()
+}
+
+fn send_response(key: &FrameKey, id: u32, result: IpcResult) {
     let call = {
         let mut map = pending_calls().lock().unwrap();
-        map.remove(&id)
+        map.remove(&(key.clone(), id))
     };
 
     let Some(call) = call else {
@@ -313,70 +655,134 @@ fn send_response(id: u32, result: IpcResult) {
         return;
     }
 
-    let mut msg = process_message_create(Some(&CefString::from("ipc"))).unwrap();
-    let args = msg.argument_list().unwrap();
-
     match result {
-        Ok(payload) => {
-            args.set_int(0, 1); // resolve
-            args.set_int(1, id as i32);
-            args.set_string(2, Some(&CefString::from(payload.as_str())));
-        }
+        Ok(payload) => match Text::outgoing(&payload) {
+            Ok((payload, shm)) => {
+                if let Some(shm) = shm {
+                    debug!("[Browser] SHM JSON response: {} bytes", shm.size());
+                    // Keep SHM alive; renderer sends SHM_FREE after reading
+                    hold_response(key, id, vec![shm]);
+                }
+
+                IpcMessage::Resolve { id, payload }.send(&call.frame, ProcessId::RENDERER);
+            }
+            Err(e) => {
+                eprintln!("[IPC] JSON response {} failed: {}", id, e);
+                let error = format!("[IPC] shm transport error: {}", e);
+                IpcMessage::Reject { id, error }.send(&call.frame, ProcessId::RENDERER);
+            }
+        },
 
-        Err(err) => {
-            args.set_int(0, 2); // reject
-            args.set_int(1, id as i32);
-            args.set_string(2, Some(&CefString::from(err.as_str())));
+        Err(error) => {
+            IpcMessage::Reject { id, error }.send(&call.frame, ProcessId::RENDERER);
         }
     }
-
-    call.frame.send_process_message(ProcessId::RENDERER, Some(&mut msg));
 }
 
 //
 // Binary response
 //
 
-fn send_binary_response(id: u32, result: Result<Vec<u8>, String>, frame: &Frame) {
+fn send_binary_response(key: &FrameKey, id: u32, result: Result<TypedBuffer, String>, frame: &Frame) {
     // Guard against destroyed frames (mirrors send_response)
     if frame.is_valid() == 0 {
         debug!("[IPC] frame destroyed before binary response id={}", id);
         return;
     }
 
-    let mut msg = process_message_create(Some(&CefString::from("ipc"))).unwrap();
-    let args = msg.argument_list().unwrap();
-
     match result {
+        Ok(typed) => {
+            // Typed responses carry dtype and shape; the bridge builds the typed array
+            let tag = match typed.tag.as_ref().map(|(dtype, shape)| type_tag(*dtype, shape)).transpose() {
+                Ok(tag) => tag,
+                Err(error) => {
+                    IpcMessage::Reject { id, error }.send(frame, ProcessId::RENDERER);
+                    return;
+                }
+            };
+
+            let (data, shm) = match Blob::outgoing(typed.as_bytes()) {
+                Ok(outgoing) => outgoing,
+                Err(e) => {
+                    eprintln!("[IPC] binary response {} failed: {}", id, e);
+                    let error = format!("[IPC] shm transport error: {}", e);
+                    IpcMessage::Reject { id, error }.send(frame, ProcessId::RENDERER);
+                    return;
+                }
+            };
 
-        Ok(data) => {
-            args.set_int(0, 4);
-            args.set_int(1, id as i32);
-
-            if data.len() < SHM_THRESHOLD {
-                debug!("[Browser] inline binary response: {} bytes", data.len());
-                let mut binary = binary_value_create(Some(data.as_slice())).unwrap();
-                args.set_binary(2, Some(&mut binary));
-
-            } else {
-                debug!("[Browser] SHM binary response: {} bytes", data.len());
-                let mut shm = SharedBuffer::create(data.len());
-                shm.write(&data);
-
-                let name = shm.name();
-                args.set_string(2, Some(&CefString::from(name.as_str())));
-                args.set_int(3, data.len() as i32);
-                // Keep SHM alive; renderer sends msg_type 5 (SHM_FREE) after reading
-                response_shm_store().lock().unwrap().insert(id, shm);
+            match shm {
+                Some(shm) => {
+                    debug!("[Browser] SHM binary response: {} bytes", shm.size());
+                    // Keep SHM alive; renderer sends SHM_FREE after reading
+                    hold_response(key, id, vec![shm]);
+                }
+                None => debug!("[Browser] inline binary response: {} bytes", data.size()),
             }
+
+            IpcMessage::ResolveBinary { id, data, tag }.send(frame, ProcessId::RENDERER);
         }
 
-        Err(err) => {
-            args.set_int(0, 2); // reject
-            args.set_int(1, id as i32);
-            args.set_string(2, Some(&CefString::from(err.as_str())));
+        Err(error) => {
+            IpcMessage::Reject { id, error }.send(frame, ProcessId::RENDERER);
         }
     }
+}
+
+/// Wire tag for a typed response. CEF lists hold i32, so larger dimensions
+/// are refused rather than truncated.
+fn type_tag(dtype: DType, shape: &[usize]) -> Result<TypeTag, String> {
+    let shape = shape
+        .iter()
+        .map(|d| i32::try_from(*d).map_err(|_| format!("TypedBuffer dimension {} exceeds {}", d, i32::MAX)))
+        .collect::<Result<_, _>>()?;
+
+    Ok(TypeTag { dtype: dtype.as_str().to_string(), shape })
+}
+
+//
+// Attachment response
+//
 
-    frame.send_process_message(ProcessId::RENDERER, Some(&mut msg));
+fn send_attachment_response(key: &FrameKey, id: u32, result: AttachmentResult, frame: &Frame) {
+    if frame.is_valid() == 0 {
+        debug!("[IPC] frame destroyed before attachment response id={}", id);
+        return;
+    }
+
+    match result {
+        Ok((meta, buffers)) => {
+            let mut attachments = Vec::with_capacity(buffers.len());
+            let mut shms = Vec::new();
+
+            for buffer in &buffers {
+                match Blob::outgoing(buffer) {
+                    Ok((blob, shm)) => {
+                        attachments.push(blob);
+                        shms.extend(shm);
+                    }
+                    Err(e) => {
+                        // Regions created so far are dropped with `shms`
+                        eprintln!("[IPC] attachment response {} failed: {}", id, e);
+                        let error = format!("[IPC] shm transport error: {}", e);
+                        IpcMessage::Reject { id, error }.send(frame, ProcessId::RENDERER);
+                        return;
+                    }
+                }
+            }
+
+            debug!("[Browser] attachment response: {} attachments, {} via SHM", attachments.len(), shms.len());
+
+            if !shms.is_empty() {
+                // Renderer sends SHM_FREE once every attachment is copied
+                hold_response(key, id, shms);
+            }
+
+            IpcMessage::ResolveAttachments { id, meta, attachments }.send(frame, ProcessId::RENDERER);
+        }
+
+        Err(error) => {
+            IpcMessage::Reject { id, error }.send(frame, ProcessId::RENDERER);
+        }
+    }
 }
@@ -0,0 +1,144 @@
+//! Payload size limits and shared memory quota for IPC.
+//!
+//! Limits are configured once through App::limits before the runtime starts.
+//! App::run executes in every CEF process, so the renderer and browser see
+//! the same configuration: the renderer refuses to send oversized requests,
+//! the browser refuses to accept them or to send oversized responses.
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+const MB: usize = 1024 * 1024;
+
+static LIMITS: OnceLock<IpcLimits> = OnceLock::new();
+
+/// Global IPC limits with optional per-command overrides.
+///
+/// Sizes are in bytes. A request is the JSON payload, the binary buffer, or the
+/// metadata plus all attachments sent by JS; a response is the same on the way back.
+/// A batch as a whole is held to the global request limit, and each call in it
+/// to the limits of its command.
+#[derive(Clone, Debug)]
+pub struct IpcLimits {
+    max_request: usize,
+    max_response: usize,
+    max_outstanding_shm: usize,
+    commands: HashMap<String, CommandLimits>,
+}
+
+/// Overrides for a single command. Unset values fall back to the global limit.
+#[derive(Clone, Debug, Default)]
+pub struct CommandLimits {
+    max_request: Option<usize>,
+    max_response: Option<usize>,
+}
+
+impl Default for IpcLimits {
+    fn default() -> Self {
+        Self {
+            max_request: 256 * MB,
+            max_response: 256 * MB,
+            max_outstanding_shm: 1024 * MB,
+            commands: HashMap::new(),
+        }
+    }
+}
+
+impl IpcLimits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Largest request any command accepts (default 256 MB).
+    pub fn max_request(mut self, bytes: usize) -> Self {
+        self.max_request = bytes;
+        self
+    }
+
+    /// Largest response any command may return (default 256 MB).
+    pub fn max_response(mut self, bytes: usize) -> Self {
+        self.max_response = bytes;
+        self
+    }
+
+    /// Cap on shared memory a process may hold for in-flight calls (default 1 GB).
+    pub fn max_outstanding_shm(mut self, bytes: usize) -> Self {
+        self.max_outstanding_shm = bytes;
+        self
+    }
+
+    /// Override the limits of one command.
+    pub fn command(mut self, name: impl Into<String>, limits: CommandLimits) -> Self {
+        self.commands.insert(name.into(), limits);
+        self
+    }
+
+    /// Check a request of `size` bytes for `command`.
+    pub(crate) fn check_request(&self, command: &str, size: usize) -> Result<(), String> {
+        let max = self
+            .commands
+            .get(command)
+            .and_then(|c| c.max_request)
+            .unwrap_or(self.max_request);
+
+        check(size, max, "request", command)
+    }
+
+    /// Check an encoded batch of `size` bytes against the global request limit.
+    pub(crate) fn check_batch(&self, size: usize) -> Result<(), String> {
+        check(size, self.max_request, "request", "batch")
+    }
+
+    /// Check a response of `size` bytes from `command`.
+    pub(crate) fn check_response(&self, command: &str, size: usize) -> Result<(), String> {
+        let max = self
+            .commands
+            .get(command)
+            .and_then(|c| c.max_response)
+            .unwrap_or(self.max_response);
+
+        check(size, max, "response", command)
+    }
+
+    pub(crate) fn outstanding_shm(&self) -> usize {
+        self.max_outstanding_shm
+    }
+}
+
+impl CommandLimits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn max_request(mut self, bytes: usize) -> Self {
+        self.max_request = Some(bytes);
+        self
+    }
+
+    pub fn max_response(mut self, bytes: usize) -> Self {
+        self.max_response = Some(bytes);
+        self
+    }
+}
+
+fn check(size: usize, max: usize, what: &str, command: &str) -> Result<(), String> {
+    if size > max {
+        return Err(format!(
+            "[IPC] {} of {} bytes for '{}' exceeds the limit of {} bytes",
+            what, size, command, max
+        ));
+    }
+    Ok(())
+}
+
+/// Install the limits. Only the first call has an effect.
+pub(crate) fn set_limits(limits: IpcLimits) {
+    if LIMITS.set(limits).is_err() {
+        eprintln!("[IPC] limits already configured, ignoring");
+    }
+}
+
+/// Active limits, or the defaults if none were configured.
+pub(crate) fn limits() -> &'static IpcLimits {
+    LIMITS.get_or_init(IpcLimits::default)
+}
@@ -0,0 +1,1068 @@
+//! IPC wire protocol shared by the browser and renderer processes.
+//!
+//! Every message is a ProcessMessage named "ipc" whose ListValue starts with
+//! `[type, id]`. The remaining slots depend on the type:
+//!
+//! | type | message            | direction          | slots after `[type, id]`            |
+//! |------|--------------------|--------------------|-------------------------------------|
+//! | 0    | Invoke             | renderer → browser | command, text                       |
+//! | 1    | Resolve            | browser → renderer | text                                |
+//! | 2    | Reject             | browser → renderer | error                               |
+//! | 3    | InvokeBinary       | renderer → browser | command, blob                       |
+//! | 4    | ResolveBinary      | browser → renderer | blob, [dtype, shape list]           |
+//! | 5    | ShmFree            | renderer → browser |                                     |
+//! | 6    | InvokeBatch        | renderer → browser | text (JSON `[[command, payload]]`)  |
+//! | 7    | InvokeAttachments  | renderer → browser | command, meta, blob list            |
+//! | 8    | ResolveAttachments | browser → renderer | meta, blob list                     |
+//! | 9    | Hello              | renderer → browser | protocol, runtime version, shm prefix |
+//! | 10   | HelloAck           | browser → renderer | protocol, runtime version, shm prefix |
+//!
+//! A *text* takes two slots: the string (or SHM name) and the SHM byte length,
+//! 0 when the string is inline. A *blob* takes two slots: a BinaryValue (or SHM
+//! name) and the byte length. CEF has no empty BinaryValue, so an empty blob
+//! is an empty string with length 0. A blob list is a nested ListValue of blobs.
+//!
+//! The handshake announces the prefix of the SHM regions each side creates;
+//! names received later must carry the prefix the sender announced.
+//!
+//! PROTOCOL_VERSION must be bumped on any incompatible change to these layouts.
+
+use std::borrow::Cow;
+use std::fmt::{Display, Formatter};
+use cef::*;
+
+use crate::ipc_shm::{Payload, SharedBuffer, SHM_THRESHOLD, validate_name, validate_prefix};
+use crate::typed_buffer::DType;
+
+pub const MESSAGE_NAME: &str = "ipc";
+
+/// Version of the layouts above. Checked by the handshake and by the JS bridge.
+pub const PROTOCOL_VERSION: i32 = 2;
+
+/// Runtime version reported during the handshake and exposed to the bridge.
+pub const RUNTIME_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Largest payload a single text or blob can carry; sizes travel as i32.
+pub const MAX_PAYLOAD_SIZE: usize = i32::MAX as usize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MessageType {
+    Invoke = 0,
+    Resolve = 1,
+    Reject = 2,
+    InvokeBinary = 3,
+    ResolveBinary = 4,
+    ShmFree = 5,
+    InvokeBatch = 6,
+    InvokeAttachments = 7,
+    ResolveAttachments = 8,
+    Hello = 9,
+    HelloAck = 10,
+}
+
+impl TryFrom<i32> for MessageType {
+    type Error = i32;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0 => MessageType::Invoke,
+            1 => MessageType::Resolve,
+            2 => MessageType::Reject,
+            3 => MessageType::InvokeBinary,
+            4 => MessageType::ResolveBinary,
+            5 => MessageType::ShmFree,
+            6 => MessageType::InvokeBatch,
+            7 => MessageType::InvokeAttachments,
+            8 => MessageType::ResolveAttachments,
+            9 => MessageType::Hello,
+            10 => MessageType::HelloAck,
+            other => return Err(other),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum ProtocolError {
+    /// Message without a `[type, id]` header.
+    NoHeader,
+    /// Type tag not defined by this protocol version.
+    UnknownType { ty: i32, id: u32 },
+    /// Known type whose arguments do not match its layout.
+    Malformed { ty: MessageType, id: u32, reason: String },
+}
+
+impl ProtocolError {
+    /// Call id the error refers to, so the caller's promise can be rejected.
+    pub fn id(&self) -> Option<u32> {
+        match self {
+            ProtocolError::NoHeader => None,
+            ProtocolError::UnknownType { id, .. } => Some(*id),
+            ProtocolError::Malformed { id, .. } => Some(*id),
+        }
+    }
+}
+
+impl Display for ProtocolError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProtocolError::NoHeader => write!(f, "IPC message without a [type, id] header"),
+            ProtocolError::UnknownType { ty, id } => write!(
+                f,
+                "unknown IPC message type {} (id={}); bridge and runtime speak different protocol versions?",
+                ty, id
+            ),
+            ProtocolError::Malformed { ty, id, reason } => {
+                write!(f, "malformed IPC {:?} message (id={}): {}", ty, id, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+/// A string carried inline or as UTF-8 bytes in shared memory.
+pub enum Text<'a> {
+    Inline(Cow<'a, str>),
+    Shared { name: String, size: usize },
+}
+
+/// Bytes carried inline in a BinaryValue or in shared memory.
+pub enum Blob<'a> {
+    Inline(Cow<'a, [u8]>),
+    Shared { name: String, size: usize },
+}
+
+/// dtype and shape of a typed binary response.
+pub struct TypeTag {
+    pub dtype: String,
+    pub shape: Vec<i32>,
+}
+
+pub enum IpcMessage<'a> {
+    Invoke { id: u32, command: String, payload: Text<'a> },
+    Resolve { id: u32, payload: Text<'a> },
+    Reject { id: u32, error: String },
+    InvokeBinary { id: u32, command: String, data: Blob<'a> },
+    ResolveBinary { id: u32, data: Blob<'a>, tag: Option<TypeTag> },
+    ShmFree { id: u32 },
+    InvokeBatch { id: u32, calls: Text<'a> },
+    InvokeAttachments { id: u32, command: String, meta: String, attachments: Vec<Blob<'a>> },
+    ResolveAttachments { id: u32, meta: String, attachments: Vec<Blob<'a>> },
+    Hello { protocol: i32, version: String, shm_prefix: String },
+    HelloAck { protocol: i32, version: String, shm_prefix: String },
+}
+
+//
+// Sender side: choose inline or SHM transport
+//
+
+/// Copy `data` into a new SHM region, refusing sizes the wire cannot describe.
+fn share(data: &[u8]) -> Result<SharedBuffer, String> {
+    if data.len() > MAX_PAYLOAD_SIZE {
+        return Err(format!(
+            "payload of {} bytes exceeds the IPC limit of {} bytes",
+            data.len(), MAX_PAYLOAD_SIZE
+        ));
+    }
+
+    let mut shm = SharedBuffer::create(data.len())?;
+    shm.write(data);
+    Ok(shm)
+}
+
+impl<'a> Text<'a> {
+    /// Inline below SHM_THRESHOLD, otherwise copied into a new SHM region that
+    /// the caller must keep alive until the peer has read it.
+    pub fn outgoing(s: &'a str) -> Result<(Self, Option<SharedBuffer>), String> {
+        if s.len() < SHM_THRESHOLD {
+            return Ok((Text::Inline(Cow::Borrowed(s)), None));
+        }
+
+        let shm = share(s.as_bytes())?;
+        Ok((Text::Shared { name: shm.name(), size: s.len() }, Some(shm)))
+    }
+
+    /// Length in bytes, known before any SHM is mapped.
+    pub fn size(&self) -> usize {
+        match self {
+            Text::Inline(s) => s.len(),
+            Text::Shared { size, .. } => *size,
+        }
+    }
+
+    /// Read the string, mapping SHM if needed. `peer` is the SHM prefix the
+    /// sender announced.
+    pub fn open(self, peer: Option<&str>) -> Result<String, String> {
+        match self {
+            Text::Inline(s) => Ok(s.into_owned()),
+            Text::Shared { name, size } => {
+                let shm = SharedBuffer::open(&name, size, peer)?;
+                String::from_utf8(shm.as_slice().to_vec())
+                    .map_err(|_| "text payload is not valid UTF-8".to_string())
+            }
+        }
+    }
+}
+
+impl<'a> Blob<'a> {
+    /// Inline below SHM_THRESHOLD, otherwise copied into a new SHM region that
+    /// the caller must keep alive until the peer has read it.
+    pub fn outgoing(data: &'a [u8]) -> Result<(Self, Option<SharedBuffer>), String> {
+        if data.len() < SHM_THRESHOLD {
+            return Ok((Blob::Inline(Cow::Borrowed(data)), None));
+        }
+
+        let shm = share(data)?;
+        Ok((Blob::Shared { name: shm.name(), size: data.len() }, Some(shm)))
+    }
+
+    /// Take the bytes, mapping SHM if needed (no copy for SHM). `peer` is
+    /// the SHM prefix the sender announced.
+    pub fn open(self, peer: Option<&str>) -> Result<Payload, String> {
+        match self {
+            Blob::Inline(data) => Ok(Payload::Inline(data.into_owned())),
+            Blob::Shared { name, size } => SharedBuffer::open(&name, size, peer).map(Payload::Shared),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        match self {
+            Blob::Inline(data) => data.len(),
+            Blob::Shared { size, .. } => *size,
+        }
+    }
+
+    /// What the first of the blob's two slots holds.
+    fn slot(&self) -> Slot<'_> {
+        match self {
+            // binary_value_create returns null for no bytes
+            Blob::Inline(data) if data.is_empty() => Slot::String(""),
+            Blob::Inline(data) => Slot::Binary(data),
+            Blob::Shared { name, .. } => Slot::String(name),
+        }
+    }
+}
+
+enum Slot<'b> {
+    Binary(&'b [u8]),
+    String(&'b str),
+}
+
+//
+// Encoding
+//
+
+impl IpcMessage<'_> {
+    pub fn message_type(&self) -> MessageType {
+        match self {
+            IpcMessage::Invoke { .. } => MessageType::Invoke,
+            IpcMessage::Resolve { .. } => MessageType::Resolve,
+            IpcMessage::Reject { .. } => MessageType::Reject,
+            IpcMessage::InvokeBinary { .. } => MessageType::InvokeBinary,
+            IpcMessage::ResolveBinary { .. } => MessageType::ResolveBinary,
+            IpcMessage::ShmFree { .. } => MessageType::ShmFree,
+            IpcMessage::InvokeBatch { .. } => MessageType::InvokeBatch,
+            IpcMessage::InvokeAttachments { .. } => MessageType::InvokeAttachments,
+            IpcMessage::ResolveAttachments { .. } => MessageType::ResolveAttachments,
+            IpcMessage::Hello { .. } => MessageType::Hello,
+            IpcMessage::HelloAck { .. } => MessageType::HelloAck,
+        }
+    }
+
+    fn id(&self) -> u32 {
+        match self {
+            IpcMessage::Invoke { id, .. }
+            | IpcMessage::Resolve { id, .. }
+            | IpcMessage::Reject { id, .. }
+            | IpcMessage::InvokeBinary { id, .. }
+            | IpcMessage::ResolveBinary { id, .. }
+            | IpcMessage::ShmFree { id }
+            | IpcMessage::InvokeBatch { id, .. }
+            | IpcMessage::InvokeAttachments { id, .. }
+            | IpcMessage::ResolveAttachments { id, .. } => *id,
+            IpcMessage::Hello { .. } | IpcMessage::HelloAck { .. } => 0,
+        }
+    }
+
+    pub fn encode(&self) -> ProcessMessage {
+        let msg = process_message_create(Some(&CefString::from(MESSAGE_NAME))).unwrap();
+        let args = msg.argument_list().unwrap();
+
+        args.set_int(0, self.message_type() as i32);
+        args.set_int(1, self.id() as i32);
+
+        match self {
+            IpcMessage::Invoke { command, payload, .. } => {
+                set_string(&args, 2, command);
+                set_text(&args, 3, payload);
+            }
+            IpcMessage::Resolve { payload, .. } => {
+                set_text(&args, 2, payload);
+            }
+            IpcMessage::Reject { error, .. } => {
+                set_string(&args, 2, error);
+            }
+            IpcMessage::InvokeBinary { command, data, .. } => {
+                set_string(&args, 2, command);
+                set_blob(&args, 3, data);
+            }
+            IpcMessage::ResolveBinary { data, tag, .. } => {
+                set_blob(&args, 2, data);
+
+                if let Some(tag) = tag {
+                    let mut dims = list_value_create().unwrap();
+                    for (i, d) in tag.shape.iter().enumerate() {
+                        dims.set_int(i, *d);
+                    }
+
+                    set_string(&args, 4, &tag.dtype);
+                    args.set_list(5, Some(&mut dims));
+                }
+            }
+            IpcMessage::ShmFree { .. } => {}
+            IpcMessage::InvokeBatch { calls, .. } => {
+                set_text(&args, 2, calls);
+            }
+            IpcMessage::InvokeAttachments { command, meta, attachments, .. } => {
+                set_string(&args, 2, command);
+                set_string(&args, 3, meta);
+                args.set_list(4, Some(&mut blob_list(attachments)));
+            }
+            IpcMessage::ResolveAttachments { meta, attachments, .. } => {
+                set_string(&args, 2, meta);
+                args.set_list(3, Some(&mut blob_list(attachments)));
+            }
+            IpcMessage::Hello { protocol, version, shm_prefix }
+            | IpcMessage::HelloAck { protocol, version, shm_prefix } => {
+                args.set_int(2, *protocol);
+                set_string(&args, 3, version);
+                set_string(&args, 4, shm_prefix);
+            }
+        }
+
+        msg
+    }
+
+    /// Encode and send to `target` through `frame`.
+    pub fn send(&self, frame: &Frame, target: ProcessId) {
+        let mut msg = self.encode();
+        frame.send_process_message(target, Some(&mut msg));
+    }
+}
+
+fn set_string(args: &ListValue, idx: usize, s: &str) {
+    args.set_string(idx, Some(&CefString::from(s)));
+}
+
+fn set_text(args: &ListValue, idx: usize, text: &Text) {
+    match text {
+        Text::Inline(s) => {
+            set_string(args, idx, s);
+            args.set_int(idx + 1, 0);
+        }
+        Text::Shared { name, size } => {
+            set_string(args, idx, name);
+            args.set_int(idx + 1, *size as i32);
+        }
+    }
+}
+
+fn set_blob(args: &ListValue, idx: usize, blob: &Blob) {
+    match blob.slot() {
+        Slot::Binary(data) => {
+            let mut binary = binary_value_create(Some(data)).unwrap();
+            args.set_binary(idx, Some(&mut binary));
+        }
+        Slot::String(s) => set_string(args, idx, s),
+    }
+
+    args.set_int(idx + 1, blob.size() as i32);
+}
+
+fn blob_list(blobs: &[Blob]) -> ListValue {
+    let list = list_value_create().unwrap();
+    for (i, blob) in blobs.iter().enumerate() {
+        set_blob(&list, i * 2, blob);
+    }
+    list
+}
+
+//
+// Decoding
+//
+// Everything below treats the ListValue as untrusted: argument counts and
+// types are checked, declared sizes are bounded, and SHM names must look
+// like SharedBuffer::create's (whose region it is gets checked on open,
+// against the prefix the peer announced). Nothing here panics on bad input.
+//
+
+/// Whether a ProcessMessage belongs to this protocol.
+pub fn is_ipc(message: &ProcessMessage) -> bool {
+    let name: CefString = (&message.name()).into();
+    name.to_string() == MESSAGE_NAME
+}
+
+/// Kinds of argument the layouts use.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kind {
+    Int,
+    String,
+    Binary,
+    List,
+    Other,
+}
+
+/// Read access to an argument list: a ListValue on the wire, plain values
+/// in the tests.
+trait Args: Sized {
+    fn size(&self) -> usize;
+    fn kind(&self, idx: usize) -> Kind;
+    fn int(&self, idx: usize) -> i32;
+    fn string(&self, idx: usize) -> String;
+    fn binary_size(&self, idx: usize) -> Option<usize>;
+    /// Copy of a binary argument's bytes.
+    fn binary(&self, idx: usize) -> Option<Vec<u8>>;
+    fn list(&self, idx: usize) -> Option<Self>;
+}
+
+impl Args for ListValue {
+    fn size(&self) -> usize {
+        ListValue::size(self)
+    }
+
+    fn kind(&self, idx: usize) -> Kind {
+        match self.type_(idx) {
+            ValueType::INT => Kind::Int,
+            ValueType::STRING => Kind::String,
+            ValueType::BINARY => Kind::Binary,
+            ValueType::LIST => Kind::List,
+            _ => Kind::Other,
+        }
+    }
+
+    fn int(&self, idx: usize) -> i32 {
+        ListValue::int(self, idx)
+    }
+
+    fn string(&self, idx: usize) -> String {
+        let s: CefString = (&ListValue::string(self, idx)).into();
+        s.to_string()
+    }
+
+    fn binary_size(&self, idx: usize) -> Option<usize> {
+        ListValue::binary(self, idx).map(|b| b.size())
+    }
+
+    fn binary(&self, idx: usize) -> Option<Vec<u8>> {
+        let binary = ListValue::binary(self, idx)?;
+
+        let mut buf = vec![0u8; binary.size()];
+        let written = binary.data(Some(&mut buf), 0);
+        buf.truncate(written);
+
+        Some(buf)
+    }
+
+    fn list(&self, idx: usize) -> Option<Self> {
+        ListValue::list(self, idx)
+    }
+}
+
+struct Reader<'l, A> {
+    args: &'l A,
+    ty: MessageType,
+    id: u32,
+}
+
+impl<A: Args> Reader<'_, A> {
+    fn malformed(&self, reason: impl Into<String>) -> ProtocolError {
+        ProtocolError::Malformed { ty: self.ty, id: self.id, reason: reason.into() }
+    }
+
+    /// Require one of the argument counts a layout allows.
+    fn expect_len(&self, allowed: &[usize]) -> Result<(), ProtocolError> {
+        let size = self.args.size();
+        if !allowed.contains(&size) {
+            return Err(self.malformed(format!("expected {:?} arguments, got {}", allowed, size)));
+        }
+        Ok(())
+    }
+
+    fn expect_kind(&self, idx: usize, expected: Kind, what: &str) -> Result<(), ProtocolError> {
+        if self.args.kind(idx) != expected {
+            return Err(self.malformed(format!("argument {} ({}) has the wrong type", idx, what)));
+        }
+        Ok(())
+    }
+
+    fn int(&self, idx: usize, what: &str) -> Result<i32, ProtocolError> {
+        self.expect_kind(idx, Kind::Int, what)?;
+        Ok(self.args.int(idx))
+    }
+
+    fn string(&self, idx: usize, what: &str) -> Result<String, ProtocolError> {
+        self.expect_kind(idx, Kind::String, what)?;
+        Ok(self.args.string(idx))
+    }
+
+    /// Byte length declared by the peer, bounded to 0..=MAX_PAYLOAD_SIZE.
+    fn size(&self, idx: usize) -> Result<usize, ProtocolError> {
+        let size = self.int(idx, "size")?;
+        usize::try_from(size)
+            .ok()
+            .filter(|s| *s <= MAX_PAYLOAD_SIZE)
+            .ok_or_else(|| self.malformed(format!("invalid size {}", size)))
+    }
+
+    fn shm_name(&self, idx: usize) -> Result<String, ProtocolError> {
+        let name = self.string(idx, "shm name")?;
+        validate_name(&name).map_err(|e| self.malformed(e))?;
+        Ok(name)
+    }
+
+    fn shm_prefix(&self, idx: usize) -> Result<String, ProtocolError> {
+        let prefix = self.string(idx, "shm prefix")?;
+        validate_prefix(&prefix).map_err(|e| self.malformed(e))?;
+        Ok(prefix)
+    }
+
+    fn text(&self, idx: usize) -> Result<Text<'static>, ProtocolError> {
+        match self.size(idx + 1)? {
+            0 => Ok(Text::Inline(Cow::Owned(self.string(idx, "text")?))),
+            size => Ok(Text::Shared { name: self.shm_name(idx)?, size }),
+        }
+    }
+
+    fn blob(&self, idx: usize) -> Result<Blob<'static>, ProtocolError> {
+        let size = self.size(idx + 1)?;
+
+        if self.args.kind(idx) != Kind::Binary {
+            if size == 0 {
+                // The empty string stands for an empty inline blob
+                return match self.string(idx, "blob")?.is_empty() {
+                    true => Ok(Blob::Inline(Cow::Borrowed(&[]))),
+                    false => Err(self.malformed("empty shared blob")),
+                };
+            }
+            return Ok(Blob::Shared { name: self.shm_name(idx)?, size });
+        }
+
+        let unreadable = || self.malformed(format!("argument {} (blob) is unreadable", idx));
+        let carried = self.args.binary_size(idx).ok_or_else(unreadable)?;
+
+        // Checked before copying, so a bogus size never allocates
+        if carried != size {
+            return Err(self.malformed(format!(
+                "blob declares {} bytes but carries {}", size, carried
+            )));
+        }
+
+        let buf = self.args.binary(idx).ok_or_else(unreadable)?;
+        Ok(Blob::Inline(Cow::Owned(buf)))
+    }
+
+    fn blob_list(&self, idx: usize) -> Result<Vec<Blob<'static>>, ProtocolError> {
+        self.expect_kind(idx, Kind::List, "blob list")?;
+
+        let list = self
+            .args
+            .list(idx)
+            .ok_or_else(|| self.malformed("missing attachment list"))?;
+
+        if list.size() % 2 != 0 {
+            return Err(self.malformed("blob list has an odd number of entries"));
+        }
+
+        let nested = Reader { args: &list, ty: self.ty, id: self.id };
+        (0..list.size() / 2).map(|i| nested.blob(i * 2)).collect()
+    }
+
+    /// Optional [dtype, shape] pair; the shape must describe exactly `byte_len` bytes.
+    fn tag(&self, idx: usize, byte_len: usize) -> Result<Option<TypeTag>, ProtocolError> {
+        if self.args.size() <= idx {
+            return Ok(None);
+        }
+
+        let dtype = self.string(idx, "dtype")?;
+        let element = DType::from_wire(&dtype)
+            .ok_or_else(|| self.malformed(format!("unknown dtype '{}'", dtype.escape_debug())))?
+            .size();
+
+        self.expect_kind(idx + 1, Kind::List, "shape")?;
+        let dims = self
+            .args
+            .list(idx + 1)
+            .ok_or_else(|| self.malformed("missing shape"))?;
+
+        let nested = Reader { args: &dims, ty: self.ty, id: self.id };
+        let shape = (0..dims.size())
+            .map(|i| {
+                nested
+                    .int(i, "dimension")
+                    .and_then(|d| if d < 0 { Err(self.malformed("negative dimension")) } else { Ok(d) })
+            })
+            .collect::<Result<Vec<i32>, _>>()?;
+
+        let described = shape
+            .iter()
+            .try_fold(element, |acc, d| acc.checked_mul(*d as usize));
+
+        if described != Some(byte_len) {
+            return Err(self.malformed(format!(
+                "shape {:?} of {} does not match {} bytes", shape, dtype, byte_len
+            )));
+        }
+
+        Ok(Some(TypeTag { dtype, shape }))
+    }
+}
+
+impl IpcMessage<'static> {
+    /// Decode an "ipc" ProcessMessage; see the module docs for the layouts.
+    pub fn decode(message: &ProcessMessage) -> Result<Self, ProtocolError> {
+        let args = message.argument_list().ok_or(ProtocolError::NoHeader)?;
+        Self::decode_args(&args)
+    }
+
+    fn decode_args(args: &impl Args) -> Result<Self, ProtocolError> {
+        if args.size() < 2
+            || args.kind(0) != Kind::Int
+            || args.kind(1) != Kind::Int
+        {
+            return Err(ProtocolError::NoHeader);
+        }
+
+        let raw_type = args.int(0);
+        let id = args.int(1) as u32;
+
+        let ty = MessageType::try_from(raw_type)
+            .map_err(|ty| ProtocolError::UnknownType { ty, id })?;
+
+        let r = Reader { args, ty, id };
+
+        Ok(match ty {
+            MessageType::Invoke => {
+                r.expect_len(&[5])?;
+                IpcMessage::Invoke { id, command: r.string(2, "command")?, payload: r.text(3)? }
+            }
+            MessageType::Resolve => {
+                r.expect_len(&[4])?;
+                IpcMessage::Resolve { id, payload: r.text(2)? }
+            }
+            MessageType::Reject => {
+                r.expect_len(&[3])?;
+                IpcMessage::Reject { id, error: r.string(2, "error")? }
+            }
+            MessageType::InvokeBinary => {
+                r.expect_len(&[5])?;
+                IpcMessage::InvokeBinary { id, command: r.string(2, "command")?, data: r.blob(3)? }
+            }
+            MessageType::ResolveBinary => {
+                r.expect_len(&[4, 6])?;
+                let data = r.blob(2)?;
+                let tag = r.tag(4, data.size())?;
+                IpcMessage::ResolveBinary { id, data, tag }
+            }
+            MessageType::ShmFree => {
+                r.expect_len(&[2])?;
+                IpcMessage::ShmFree { id }
+            }
+            MessageType::InvokeBatch => {
+                r.expect_len(&[4])?;
+                IpcMessage::InvokeBatch { id, calls: r.text(2)? }
+            }
+            MessageType::InvokeAttachments => {
+                r.expect_len(&[5])?;
+                IpcMessage::InvokeAttachments {
+                    id,
+                    command: r.string(2, "command")?,
+                    meta: r.string(3, "meta")?,
+                    attachments: r.blob_list(4)?,
+                }
+            }
+            MessageType::ResolveAttachments => {
+                r.expect_len(&[4])?;
+                IpcMessage::ResolveAttachments {
+                    id,
+                    meta: r.string(2, "meta")?,
+                    attachments: r.blob_list(3)?,
+                }
+            }
+            MessageType::Hello => {
+                r.expect_len(&[5])?;
+                IpcMessage::Hello {
+                    protocol: r.int(2, "protocol")?,
+                    version: r.string(3, "version")?,
+                    shm_prefix: r.shm_prefix(4)?,
+                }
+            }
+            MessageType::HelloAck => {
+                r.expect_len(&[5])?;
+                IpcMessage::HelloAck {
+                    protocol: r.int(2, "protocol")?,
+                    version: r.string(3, "version")?,
+                    shm_prefix: r.shm_prefix(4)?,
+                }
+            }
+        })
+    }
+}
+
+/// Describe a protocol mismatch found by the handshake, or None if compatible.
+pub fn check_peer(protocol: i32, version: &str) -> Option<String> {
+    (protocol != PROTOCOL_VERSION).then(|| {
+        format!(
+            "IPC protocol mismatch: this process speaks v{} (kurogane {}), peer speaks v{} (kurogane {})",
+            PROTOCOL_VERSION, RUNTIME_VERSION, protocol, version
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    /// Argument list without CEF, mirroring the ValueTypes the layouts use.
+    #[derive(Clone, Debug, PartialEq)]
+    enum Arg {
+        Int(i32),
+        Str(String),
+        Bin(Vec<u8>),
+        List(Vec<Arg>),
+        Null,
+    }
+
+    impl Args for Vec<Arg> {
+        fn size(&self) -> usize {
+            self.len()
+        }
+
+        fn kind(&self, idx: usize) -> Kind {
+            match self.get(idx) {
+                Some(Arg::Int(_)) => Kind::Int,
+                Some(Arg::Str(_)) => Kind::String,
+                Some(Arg::Bin(_)) => Kind::Binary,
+                Some(Arg::List(_)) => Kind::List,
+                Some(Arg::Null) | None => Kind::Other,
+            }
+        }
+
+        // ListValue returns defaults for mismatched or missing entries
+        fn int(&self, idx: usize) -> i32 {
+            match self.get(idx) {
+                Some(Arg::Int(i)) => *i,
+                _ => 0,
+            }
+        }
+
+        fn string(&self, idx: usize) -> String {
+            match self.get(idx) {
+                Some(Arg::Str(s)) => s.clone(),
+                _ => String::new(),
+            }
+        }
+
+        fn binary_size(&self, idx: usize) -> Option<usize> {
+            self.binary(idx).map(|b| b.len())
+        }
+
+        fn binary(&self, idx: usize) -> Option<Vec<u8>> {
+            match self.get(idx) {
+                Some(Arg::Bin(b)) => Some(b.clone()),
+                _ => None,
+            }
+        }
+
+        fn list(&self, idx: usize) -> Option<Self> {
+            match self.get(idx) {
+                Some(Arg::List(l)) => Some(l.clone()),
+                _ => None,
+            }
+        }
+    }
+
+    const SHM: &str = "k_00001f2e_3";
+
+    fn int(i: i32) -> Arg {
+        Arg::Int(i)
+    }
+
+    fn s(s: &str) -> Arg {
+        Arg::Str(s.to_string())
+    }
+
+    fn header(ty: MessageType, id: i32) -> Vec<Arg> {
+        vec![int(ty as i32), int(id)]
+    }
+
+    fn with(ty: MessageType, rest: Vec<Arg>) -> Vec<Arg> {
+        let mut args = header(ty, 7);
+        args.extend(rest);
+        args
+    }
+
+    fn decode(args: Vec<Arg>) -> Result<IpcMessage<'static>, ProtocolError> {
+        IpcMessage::decode_args(&args)
+    }
+
+    fn malformed(args: Vec<Arg>) -> String {
+        match decode(args) {
+            Err(ProtocolError::Malformed { id: 7, reason, .. }) => reason,
+            Err(e) => panic!("expected a malformed message, got {}", e),
+            Ok(_) => panic!("expected a malformed message, got a decoded one"),
+        }
+    }
+
+    fn resolve_binary(bytes: usize, dtype: &str, shape: Vec<Arg>) -> Vec<Arg> {
+        with(
+            MessageType::ResolveBinary,
+            vec![Arg::Bin(vec![0; bytes]), int(bytes as i32), s(dtype), Arg::List(shape)],
+        )
+    }
+
+    /// A valid message of every type, for the mutation tests.
+    fn valid() -> Vec<Vec<Arg>> {
+        let prefix = "k_00001f2e_";
+        vec![
+            with(MessageType::Invoke, vec![s("greet"), s("{}"), int(0)]),
+            with(MessageType::Resolve, vec![s(SHM), int(12)]),
+            with(MessageType::Reject, vec![s("nope")]),
+            with(MessageType::InvokeBinary, vec![s("sum"), Arg::Bin(vec![1, 2, 3]), int(3)]),
+            resolve_binary(8, "u16", vec![int(2), int(2)]),
+            with(MessageType::ShmFree, vec![]),
+            with(MessageType::InvokeBatch, vec![s("[]"), int(0)]),
+            with(
+                MessageType::InvokeAttachments,
+                vec![s("upload"), s("{}"), Arg::List(vec![Arg::Bin(vec![9]), int(1), s(SHM), int(4)])],
+            ),
+            with(MessageType::ResolveAttachments, vec![s("{}"), Arg::List(vec![])]),
+            with(MessageType::Hello, vec![int(PROTOCOL_VERSION), s("0.0.1"), s(prefix)]),
+            with(MessageType::HelloAck, vec![int(PROTOCOL_VERSION), s("0.0.1"), s(prefix)]),
+        ]
+    }
+
+    #[test]
+    fn decodes_valid_messages() {
+        for args in valid() {
+            let ty = args[0].clone();
+            assert!(decode(args).is_ok(), "type {:?} did not decode", ty);
+        }
+    }
+
+    #[test]
+    fn decodes_typed_binary() {
+        let Ok(IpcMessage::ResolveBinary { data, tag: Some(tag), .. }) =
+            decode(resolve_binary(24, "f64", vec![int(3)]))
+        else {
+            panic!("expected a tagged binary");
+        };
+        assert_eq!(data.size(), 24);
+        assert_eq!(tag.dtype, "f64");
+        assert_eq!(tag.shape, vec![3]);
+    }
+
+    #[test]
+    fn rejects_missing_header() {
+        assert!(matches!(decode(vec![]), Err(ProtocolError::NoHeader)));
+        assert!(matches!(decode(vec![int(0)]), Err(ProtocolError::NoHeader)));
+        assert!(matches!(decode(vec![s("0"), int(1)]), Err(ProtocolError::NoHeader)));
+        assert!(matches!(decode(vec![int(0), Arg::Null]), Err(ProtocolError::NoHeader)));
+    }
+
+    #[test]
+    fn rejects_unknown_types() {
+        for ty in [-1, 11, i32::MAX, i32::MIN] {
+            assert!(matches!(
+                decode(vec![int(ty), int(3)]),
+                Err(ProtocolError::UnknownType { ty: t, id: 3 }) if t == ty
+            ));
+        }
+    }
+
+    #[test]
+    fn rejects_wrong_counts() {
+        for mut args in valid() {
+            args.push(int(0));
+            malformed(args);
+        }
+
+        for mut args in valid() {
+            if args.len() > 2 {
+                args.pop();
+                malformed(args);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_wrong_types() {
+        let reason = malformed(with(MessageType::Invoke, vec![int(1), s("{}"), int(0)]));
+        assert!(reason.contains("command"), "{}", reason);
+
+        malformed(with(MessageType::Invoke, vec![s("greet"), int(1), int(0)]));
+        malformed(with(MessageType::Invoke, vec![s("greet"), s("{}"), s("0")]));
+        malformed(with(MessageType::Reject, vec![Arg::Bin(vec![])]));
+        malformed(with(MessageType::InvokeAttachments, vec![s("up"), s("{}"), s("[]")]));
+        malformed(with(MessageType::Hello, vec![s("2"), s("0.0.1"), s("k_00000001_")]));
+    }
+
+    #[test]
+    fn rejects_bad_sizes() {
+        let reason = malformed(with(MessageType::Resolve, vec![s(SHM), int(-1)]));
+        assert!(reason.contains("invalid size -1"), "{}", reason);
+
+        malformed(with(MessageType::Resolve, vec![s(SHM), int(i32::MIN)]));
+
+        // Declared and carried sizes must agree
+        let reason = malformed(with(MessageType::InvokeBinary, vec![s("sum"), Arg::Bin(vec![1, 2]), int(3)]));
+        assert!(reason.contains("declares 3 bytes but carries 2"), "{}", reason);
+        malformed(with(MessageType::InvokeBinary, vec![s("sum"), Arg::Bin(vec![1]), int(i32::MAX)]));
+
+        // A shared blob with no bytes
+        malformed(with(MessageType::InvokeBinary, vec![s("sum"), s(SHM), int(0)]));
+        malformed(with(MessageType::InvokeBinary, vec![s("sum"), s(""), int(1)]));
+        malformed(with(MessageType::InvokeBinary, vec![s("sum"), int(0), int(0)]));
+    }
+
+    /// The two slots set_blob writes for `blob`.
+    fn encode_blob(blob: &Blob) -> Vec<Arg> {
+        let slot = match blob.slot() {
+            Slot::Binary(data) => Arg::Bin(data.to_vec()),
+            Slot::String(s) => Arg::Str(s.to_string()),
+        };
+        vec![slot, int(blob.size() as i32)]
+    }
+
+    #[test]
+    fn round_trips_empty_blobs() {
+        let empty = Blob::outgoing(&[]).unwrap().0;
+        assert_eq!(encode_blob(&empty), vec![s(""), int(0)]);
+
+        let mut args = with(MessageType::InvokeBinary, vec![s("sum")]);
+        args.extend(encode_blob(&empty));
+
+        match decode(args) {
+            Ok(IpcMessage::InvokeBinary { data, .. }) => {
+                assert_eq!(data.open(None).unwrap().as_slice(), b"");
+            }
+            other => panic!("unexpected {:?}", other.err()),
+        }
+
+        // Empty and non-empty attachments side by side
+        let blobs = [Blob::outgoing(&[]).unwrap().0, Blob::outgoing(&[1, 2]).unwrap().0];
+        let list = blobs.iter().flat_map(encode_blob).collect();
+
+        match decode(with(MessageType::ResolveAttachments, vec![s("{}"), Arg::List(list)])) {
+            Ok(IpcMessage::ResolveAttachments { attachments, .. }) => {
+                let sizes: Vec<usize> = attachments.iter().map(Blob::size).collect();
+                assert_eq!(sizes, [0, 2]);
+            }
+            other => panic!("unexpected {:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn rejects_bad_shm_names() {
+        let too_long = format!("k_00001f2e_{}", "a".repeat(64));
+        for name in ["", "k_", "other_1_2_3", "/k_00001f2e_1", "k_1/../x", "k_zz", too_long.as_str()] {
+            malformed(with(MessageType::Resolve, vec![s(name), int(4)]));
+            malformed(with(MessageType::InvokeBinary, vec![s("sum"), s(name), int(4)]));
+        }
+
+        for prefix in ["", "k_", "k_00001f2e", "k_00001f2e_3_", "k__00001f2e_", "kurogane_1_2_", "other_00001f2e_"] {
+            malformed(with(MessageType::Hello, vec![int(PROTOCOL_VERSION), s("0.0.1"), s(prefix)]));
+        }
+
+        let own = crate::ipc_shm::own_prefix();
+        malformed(with(MessageType::HelloAck, vec![int(PROTOCOL_VERSION), s("0.0.1"), s(own)]));
+    }
+
+    #[test]
+    fn rejects_bad_blob_lists() {
+        let reason = malformed(with(
+            MessageType::ResolveAttachments,
+            vec![s("{}"), Arg::List(vec![Arg::Bin(vec![1]), int(1), Arg::Bin(vec![])])],
+        ));
+        assert!(reason.contains("odd"), "{}", reason);
+
+        malformed(with(
+            MessageType::ResolveAttachments,
+            vec![s("{}"), Arg::List(vec![Arg::Bin(vec![1]), int(-1)])],
+        ));
+        malformed(with(
+            MessageType::ResolveAttachments,
+            vec![s("{}"), Arg::List(vec![s("nope"), int(4)])],
+        ));
+    }
+
+    #[test]
+    fn rejects_dtype_shape_mismatches() {
+        let reason = malformed(resolve_binary(8, "f16", vec![int(4)]));
+        assert!(reason.contains("unknown dtype"), "{}", reason);
+
+        let reason = malformed(resolve_binary(8, "u16", vec![int(2), int(3)]));
+        assert!(reason.contains("does not match 8 bytes"), "{}", reason);
+
+        malformed(resolve_binary(8, "f64", vec![int(2)]));
+        malformed(resolve_binary(8, "u8", vec![int(-8)]));
+        malformed(resolve_binary(8, "u8", vec![int(2), int(-4), int(-1)]));
+        malformed(resolve_binary(8, "u8", vec![s("8")]));
+        malformed(resolve_binary(0, "u64", vec![int(i32::MAX), int(i32::MAX), int(i32::MAX)]));
+        malformed(with(
+            MessageType::ResolveBinary,
+            vec![Arg::Bin(vec![0; 4]), int(4), s("u8"), int(4)],
+        ));
+
+        // An empty shape is a scalar
+        assert!(decode(resolve_binary(4, "f32", vec![])).is_ok());
+        malformed(resolve_binary(8, "f32", vec![]));
+    }
+
+    fn arg() -> impl Strategy<Value = Arg> {
+        let leaf = prop_oneof![
+            prop_oneof![Just(0), Just(-1), Just(i32::MAX), Just(i32::MIN), -4..16i32].prop_map(Arg::Int),
+            prop_oneof![
+                Just(SHM.to_string()),
+                Just("u8".to_string()),
+                Just("k_00001f2e_".to_string()),
+                ".{0,80}",
+            ]
+            .prop_map(Arg::Str),
+            prop::collection::vec(any::<u8>(), 0..16).prop_map(Arg::Bin),
+            Just(Arg::Null),
+        ];
+
+        leaf.prop_recursive(3, 32, 6, |inner| prop::collection::vec(inner, 0..6).prop_map(Arg::List))
+    }
+
+    proptest! {
+        #[test]
+        fn arbitrary_lists_never_panic(ty in -1..12i32, rest in prop::collection::vec(arg(), 0..8)) {
+            let mut args = vec![int(ty), int(7)];
+            args.extend(rest);
+            let _ = decode(args);
+        }
+
+        #[test]
+        fn mutated_messages_never_panic(
+            which in 0..11usize,
+            at in 0..8usize,
+            replacement in arg(),
+            truncate in any::<bool>(),
+        ) {
+            let mut args = valid().swap_remove(which);
+            let at = at % (args.len() + 1);
+
+            if truncate {
+                args.truncate(at);
+            } else if at < args.len() {
+                args[at] = replacement;
+            } else {
+                args.push(replacement);
+            }
+
+            let _ = decode(args);
+        }
+    }
+}
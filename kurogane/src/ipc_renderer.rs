@@ -1,11 +1,18 @@
 //! Renderer process IPC implementation.
-//! Transport uses ProcessMessage "ipc" and ListValue typed args.
+//! Transport uses ProcessMessage "ipc" and ListValue typed args, see ipc_protocol.
 
 use cef::*;
-use std::sync::{Mutex, OnceLock};
-use std::collections::HashMap;
-
-use crate::ipc_shm::{SharedBuffer, SHM_THRESHOLD};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::collections::{HashMap, HashSet};
+
+use crate::ipc_protocol::{
+    Blob, IpcMessage, Text, TypeTag,
+    PROTOCOL_VERSION, RUNTIME_VERSION,
+    check_peer, is_ipc,
+};
+use crate::ipc_limits::limits;
+use crate::ipc_shm::{Payload, SharedBuffer, own_prefix};
 use crate::debug;
 
 //
@@ -57,7 +64,9 @@ impl PromiseRegistry {
         }
     }
 
-    fn resolve_binary(id: u32, payload: &[u8]) {
+    /// Resolve with an ArrayBuffer, or with { buffer, dtype, shape } for typed
+    /// responses; the bridge turns the latter into the matching typed array.
+    fn resolve_binary(id: u32, payload: &[u8], tag: Option<&TypeTag>) {
         let entry = registry().lock().unwrap().pending.remove(&id);
 
         if let Some((context, promise)) = entry {
@@ -70,15 +79,167 @@ impl PromiseRegistry {
                 payload.len(),
             ).unwrap();
 
-            promise.resolve_promise(Some(&mut buf));
+            match tag {
+                None => {
+                    promise.resolve_promise(Some(&mut buf));
+                }
+                Some(tag) => {
+                    let mut typed = typed_result(tag, &mut buf);
+                    promise.resolve_promise(Some(&mut typed));
+                }
+            }
 
             context.exit(); // safe; lock not held
         }
     }
+
+    /// Resolve a SHM response without copying it into the V8 heap.
+    ///
+    /// The ArrayBuffer is created directly over the mapping and SHM_FREE is sent
+    /// through `frame` once V8 garbage-collects it. V8 builds with the memory
+    /// sandbox enabled refuse external backing stores; the first refusal is
+    /// remembered and every zero-copy response after it is copied instead.
+    fn resolve_binary_shared(id: u32, shm: SharedBuffer, tag: Option<&TypeTag>, frame: Frame) {
+        let entry = registry().lock().unwrap().pending.remove(&id);
+
+        let Some((context, promise)) = entry else {
+            release_response_shm(id, shm, &frame);
+            return;
+        };
+
+        if context.enter() == 0 {
+            eprintln!("[IPC] Failed to enter V8 context for binary promise id={}", id);
+            release_response_shm(id, shm, &frame);
+            return;
+        }
+
+        let ptr = shm.as_ptr();
+        let len = shm.size();
+
+        let holder = Arc::new(Mutex::new(Some(shm)));
+
+        let external = if EXTERNAL_BUFFERS_REJECTED.load(Ordering::Relaxed) {
+            None
+        } else {
+            let mut release = ShmReleaseCallback::new(id, holder.clone(), frame.clone());
+            let buf = v8_value_create_array_buffer(ptr, len, Some(&mut release));
+
+            if buf.is_none() {
+                eprintln!("[IPC] V8 refuses external ArrayBuffers (memory sandbox); zero-copy responses will be copied");
+                EXTERNAL_BUFFERS_REJECTED.store(true, Ordering::Relaxed);
+            }
+            buf
+        };
+
+        let mut buf = match external {
+            Some(buf) => {
+                debug!("[Renderer] zero-copy binary response: {} bytes", len);
+                ZERO_COPY_DELIVERED.fetch_add(1, Ordering::Relaxed);
+                buf
+            }
+            None => {
+                debug!("[Renderer] zero-copy unavailable, copying {} bytes", len);
+                ZERO_COPY_COPIED.fetch_add(1, Ordering::Relaxed);
+                let buf = v8_value_create_array_buffer_with_copy(ptr, len).unwrap();
+                if let Some(shm) = holder.lock().unwrap().take() {
+                    release_response_shm(id, shm, &frame);
+                }
+                buf
+            }
+        };
+
+        match tag {
+            None => {
+                promise.resolve_promise(Some(&mut buf));
+            }
+            Some(tag) => {
+                let mut typed = typed_result(tag, &mut buf);
+                promise.resolve_promise(Some(&mut typed));
+            }
+        }
+
+        context.exit(); // safe; lock not held
+    }
+
+    /// Resolve with [metaJson, [ArrayBuffer, ...]]; the bridge unpacks it.
+    fn resolve_attachments(id: u32, meta: &CefString, attachments: &[Payload]) {
+        let entry = registry().lock().unwrap().pending.remove(&id);
+
+        if let Some((context, promise)) = entry {
+            if context.enter() == 0 {
+                eprintln!("[IPC] Failed to enter V8 context for attachment promise id={}", id);
+                return;
+            }
+
+            let mut buffers = v8_value_create_array(attachments.len() as i32).unwrap();
+            for (i, payload) in attachments.iter().enumerate() {
+                let data = payload.as_slice();
+                let mut buf = v8_value_create_array_buffer_with_copy(
+                    data.as_ptr() as *mut u8,
+                    data.len(),
+                ).unwrap();
+                buffers.set_value_byindex(i as i32, Some(&mut buf));
+            }
+
+            let mut meta = v8_value_create_string(Some(meta)).unwrap();
+
+            let mut result = v8_value_create_array(2).unwrap();
+            result.set_value_byindex(0, Some(&mut meta));
+            result.set_value_byindex(1, Some(&mut buffers));
+
+            promise.resolve_promise(Some(&mut result));
+
+            context.exit(); // safe; lock not held
+        }
+    }
+}
+
+/// Wrap a typed binary response as { buffer, dtype, shape } for the bridge.
+/// Must be called inside an entered V8 context.
+fn typed_result(tag: &TypeTag, buffer: &mut V8Value) -> V8Value {
+    let obj = v8_value_create_object(None, None).unwrap();
+
+    let mut shape = v8_value_create_array(tag.shape.len() as i32).unwrap();
+    for (i, d) in tag.shape.iter().enumerate() {
+        let mut v = v8_value_create_int(*d).unwrap();
+        shape.set_value_byindex(i as i32, Some(&mut v));
+    }
+
+    let mut dtype = v8_value_create_string(Some(&CefString::from(tag.dtype.as_str()))).unwrap();
+
+    obj.set_value_bykey(Some(&CefString::from("buffer")), Some(buffer), V8Propertyattribute::default());
+    obj.set_value_bykey(Some(&CefString::from("dtype")), Some(&mut dtype), V8Propertyattribute::default());
+    obj.set_value_bykey(Some(&CefString::from("shape")), Some(&mut shape), V8Propertyattribute::default());
+
+    obj
 }
 
 const KUROGANE_BRIDGE: &str = include_str!("../bridge/runtime.js");
 
+// Set when the handshake finds a browser process speaking another protocol version
+static PROTOCOL_ERROR: OnceLock<Mutex<Option<String>>> = OnceLock::new();
+
+fn protocol_error() -> &'static Mutex<Option<String>> {
+    PROTOCOL_ERROR.get_or_init(|| Mutex::new(None))
+}
+
+/// Error to raise in JS if the browser process is incompatible.
+fn check_protocol() -> Option<String> {
+    protocol_error().lock().unwrap().clone()
+}
+
+// SHM prefix the browser process announced in HELLO_ACK
+static BROWSER_SHM_PREFIX: OnceLock<String> = OnceLock::new();
+
+/// Prefix response regions must carry, or None if `id` is not a call we are
+/// waiting for (SHM is only mapped for calls this renderer made).
+fn response_peer(id: u32) -> Option<&'static str> {
+    if !registry().lock().unwrap().pending.contains_key(&id) {
+        return None;
+    }
+    BROWSER_SHM_PREFIX.get().map(String::as_str)
+}
+
 static PROMISE_REGISTRY: OnceLock<Mutex<PromiseRegistry>> = OnceLock::new();
 
 fn registry() -> &'static Mutex<PromiseRegistry> {
@@ -105,51 +266,126 @@ fn clear_context_promises(ctx: &V8Context) {
 // proving the browser has already read the data.
 //
 
-static OUTGOING_SHM: OnceLock<Mutex<HashMap<u32, SharedBuffer>>> = OnceLock::new();
+static OUTGOING_SHM: OnceLock<Mutex<HashMap<u32, Vec<SharedBuffer>>>> = OnceLock::new();
 
 static RENDERER_FRAME: OnceLock<Mutex<Option<Frame>>> = OnceLock::new();
 
+// Binary calls that opted into zero-copy delivery of SHM responses
+static ZERO_COPY_CALLS: OnceLock<Mutex<HashSet<u32>>> = OnceLock::new();
+
+// How zero-copy responses were actually delivered, reported by core.zeroCopyStats()
+static ZERO_COPY_DELIVERED: AtomicU64 = AtomicU64::new(0);
+static ZERO_COPY_COPIED: AtomicU64 = AtomicU64::new(0);
+
+// Set once V8 refuses an external backing store (V8 memory sandbox)
+static EXTERNAL_BUFFERS_REJECTED: AtomicBool = AtomicBool::new(false);
+
 fn renderer_frame() -> &'static Mutex<Option<Frame>> {
     RENDERER_FRAME.get_or_init(|| Mutex::new(None))
 }
 
-fn outgoing_shm() -> &'static Mutex<HashMap<u32, SharedBuffer>> {
+fn outgoing_shm() -> &'static Mutex<HashMap<u32, Vec<SharedBuffer>>> {
     OUTGOING_SHM.get_or_init(|| Mutex::new(HashMap::new()))
 }
 
+fn zero_copy_calls() -> &'static Mutex<HashSet<u32>> {
+    ZERO_COPY_CALLS.get_or_init(|| Mutex::new(HashSet::new()))
+}
+
 #[inline(always)]
 fn get_frame() -> Option<Frame> {
     renderer_frame().lock().unwrap().clone()
 }
 
 /// Notify the browser that it can release its SHM response buffer.
-fn send_shm_free(id: u32, frame: &mut Frame) {
-    let mut msg = process_message_create(Some(&CefString::from("ipc"))).unwrap();
-    let args = msg.argument_list().unwrap();
-    args.set_int(0, 5); // SHM_FREE
-    args.set_int(1, id as i32);
-    frame.send_process_message(ProcessId::BROWSER, Some(&mut msg));
+fn send_shm_free(id: u32, frame: &Frame) {
+    IpcMessage::ShmFree { id }.send(frame, ProcessId::BROWSER);
     debug!("[Renderer] SHM_FREE sent for id={}", id);
 }
 
+/// Unmap a SHM response and let the browser release its side.
+/// `frame` is the frame that made the call; the browser holds the response under it.
+fn release_response_shm(id: u32, shm: SharedBuffer, frame: &Frame) {
+    drop(shm);
+
+    // A detached frame's responses were already released by the browser
+    if frame.is_valid() == 0 {
+        debug!("[Renderer] frame gone, browser already released SHM for id={}", id);
+        return;
+    }
+    send_shm_free(id, frame);
+}
+
 //
-// Helpers
+// Zero-copy release: V8 calls this when an ArrayBuffer backed by SHM is collected.
+// The calling frame is kept with the buffer, so SHM_FREE reaches the browser even
+// after the JS context is released.
 //
 
-#[inline(always)]
-fn list_int(args: &ListValue, idx: usize) -> i32 { args.int(idx) }
+wrap_v8_array_buffer_release_callback! {
+    pub struct ShmReleaseCallback {
+        id: u32,
+        shm: Arc<Mutex<Option<SharedBuffer>>>,
+        frame: Frame,
+    }
 
-#[inline(always)]
-fn list_cef_string(args: &ListValue, idx: usize) -> CefString {
-    (&args.string(idx)).into()
+    impl V8ArrayBufferReleaseCallback {
+        fn release_buffer(&self, _buffer: *mut u8) {
+            if let Some(shm) = self.shm.lock().unwrap().take() {
+                debug!("[Renderer] zero-copy buffer collected for id={}", self.id);
+                release_response_shm(self.id, shm, &self.frame);
+            }
+        }
+    }
 }
 
+//
+// Helpers
+//
+
 #[inline(always)]
 fn v8_to_string(v: &V8Value) -> String {
     let s: CefString = (&v.string_value()).into();
     s.to_string()
 }
 
+/// Inline JSON text, or SHM above SHM_THRESHOLD.
+/// The SHM is kept alive in OUTGOING_SHM until the browser responds.
+fn outgoing_text(id: u32, payload: &str) -> Result<Text<'_>, String> {
+    let (text, shm) = Text::outgoing(payload)?;
+
+    if let Some(shm) = shm {
+        debug!("[Renderer] SHM JSON payload: {} bytes", shm.size());
+        outgoing_shm().lock().unwrap().insert(id, vec![shm]);
+    }
+
+    Ok(text)
+}
+
+/// Reject a call that could not be sent to the browser.
+fn fail_call(id: u32, error: &str) {
+    eprintln!("[IPC] call {} not sent: {}", id, error);
+    outgoing_shm().lock().unwrap().remove(&id);
+    zero_copy_calls().lock().unwrap().remove(&id);
+
+    let msg = CefString::from(format!("[IPC] shm transport error: {}", error).as_str());
+    PromiseRegistry::resolve_cef_string(id, false, &msg);
+}
+
+/// Create a JS function backed by a V8 handler and attach it to `target`.
+fn set_function(target: &mut V8Value, name: &str, handler: &mut V8Handler) {
+    let mut function = v8_value_create_function(
+        Some(&CefString::from(name)),
+        Some(handler),
+    ).unwrap();
+
+    target.set_value_bykey(
+        Some(&CefString::from(name)),
+        Some(&mut function),
+        V8Propertyattribute::default(),
+    );
+}
+
 //
 // Renderer process handler
 //
@@ -170,33 +406,44 @@ wrap_render_process_handler! {
 
             *renderer_frame().lock().unwrap() = Some(frame.clone());
 
+            // Handshake: the browser answers with HELLO_ACK
+            IpcMessage::Hello {
+                protocol: PROTOCOL_VERSION,
+                version: RUNTIME_VERSION.to_string(),
+                shm_prefix: own_prefix().to_string(),
+            }.send(frame, ProcessId::BROWSER);
+
             let global = context.global().unwrap();
 
             let mut core = v8_value_create_object(None, None).unwrap();
 
             // JSON invoke
-            let mut handler = IpcInvokeHandler::new();
-            let mut invoke = v8_value_create_function(
-                Some(&CefString::from("invoke")),
-                Some(&mut handler),
-            ).unwrap();
+            set_function(&mut core, "invoke", &mut IpcInvokeHandler::new());
+
+            // Binary invoke
+            set_function(&mut core, "invokeBinary", &mut IpcInvokeBinaryHandler::new());
+
+            // Batched JSON invoke
+            set_function(&mut core, "invokeBatch", &mut IpcInvokeBatchHandler::new());
+
+            // Invoke with JSON metadata plus binary attachments
+            set_function(&mut core, "invokeAttachments", &mut IpcInvokeAttachmentsHandler::new());
+
+            // How zero-copy responses were delivered, for benchmarks and diagnostics
+            set_function(&mut core, "zeroCopyStats", &mut ZeroCopyStatsHandler::new());
 
+            // Protocol and runtime version, checked by the bridge
+            let mut protocol = v8_value_create_int(PROTOCOL_VERSION).unwrap();
             core.set_value_bykey(
-                Some(&CefString::from("invoke")),
-                Some(&mut invoke),
+                Some(&CefString::from("protocol")),
+                Some(&mut protocol),
                 V8Propertyattribute::default(),
             );
 
-            // Binary invoke
-            let mut bin_handler = IpcInvokeBinaryHandler::new();
-            let mut invoke_binary = v8_value_create_function(
-                Some(&CefString::from("invokeBinary")),
-                Some(&mut bin_handler),
-            ).unwrap();
-
+            let mut version = v8_value_create_string(Some(&CefString::from(RUNTIME_VERSION))).unwrap();
             core.set_value_bykey(
-                Some(&CefString::from("invokeBinary")),
-                Some(&mut invoke_binary),
+                Some(&CefString::from("version")),
+                Some(&mut version),
                 V8Propertyattribute::default(),
             );
 
@@ -237,73 +484,136 @@ wrap_render_process_handler! {
             if source_process != ProcessId::BROWSER { return 0; }
 
             let msg = message.unwrap();
-            let name: CefString = (&msg.name()).into();
-            if name.to_string() != "ipc" { return 0; }
-
-            let args = msg.argument_list().unwrap();
-
-            let msg_type = list_int(&args, 0);
-            let id = list_int(&args, 1) as u32;
+            if !is_ipc(msg) { return 0; }
+
+            let msg = match IpcMessage::decode(msg) {
+                Ok(msg) => msg,
+                Err(e) => {
+                    eprintln!("[IPC ERROR] {}", e);
+                    if let Some(id) = e.id() {
+                        outgoing_shm().lock().unwrap().remove(&id);
+                        zero_copy_calls().lock().unwrap().remove(&id);
+                        PromiseRegistry::resolve_cef_string(id, false, &CefString::from(e.to_string().as_str()));
+                    }
+                    return 1;
+                }
+            };
 
-            match msg_type {
-                1 => {
+            match msg {
+                IpcMessage::Resolve { id, payload } => {
                     // Release outgoing SHM; browser has read it and responded
                     outgoing_shm().lock().unwrap().remove(&id);
-                    let payload = list_cef_string(&args, 2);
-                    PromiseRegistry::resolve_cef_string(id, true, &payload);
+
+                    let shared = matches!(payload, Text::Shared { .. });
+
+                    match payload.open(response_peer(id)) {
+                        Ok(json) => {
+                            if shared {
+                                debug!("[Renderer] SHM JSON response: {} bytes", json.len());
+                            }
+                            PromiseRegistry::resolve_cef_string(id, true, &CefString::from(json.as_str()));
+                        }
+                        Err(e) => {
+                            eprintln!("[IPC] SHM JSON response failed for id={}: {}", id, e);
+                            let msg = CefString::from(format!("shm transport error: {}", e).as_str());
+                            PromiseRegistry::resolve_cef_string(id, false, &msg);
+                        }
+                    }
+
+                    // Browser used SHM for a large JSON response
+                    if shared {
+                        if let Some(f) = frame { send_shm_free(id, f); }
+                    }
                 }
 
-                2 => {
+                IpcMessage::Reject { id, error } => {
                     outgoing_shm().lock().unwrap().remove(&id);
-                    let payload = list_cef_string(&args, 2);
-                    PromiseRegistry::resolve_cef_string(id, false, &payload);
+                    zero_copy_calls().lock().unwrap().remove(&id);
+                    PromiseRegistry::resolve_cef_string(id, false, &CefString::from(error.as_str()));
                 }
 
-                4 => {
+                IpcMessage::ResolveBinary { id, data, tag } => {
                     // Release outgoing SHM regardless of transport used in response
                     outgoing_shm().lock().unwrap().remove(&id);
 
-                    if let Some(binary) = args.binary(2) {
-
-                        let size = binary.size();
-                        let mut buf = vec![0u8; size];
+                    let zero_copy = zero_copy_calls().lock().unwrap().remove(&id);
 
-                        let written = binary.data(Some(&mut buf), 0);
-                        buf.truncate(written);
+                    // The frame travels with a zero-copy buffer to send SHM_FREE on collection
+                    let release_frame = frame.as_deref().filter(|_| zero_copy).cloned();
 
-                        debug!("[Renderer] inline binary response: {} bytes", written);
+                    match (data.open(response_peer(id)), release_frame) {
+                        (Ok(Payload::Shared(shm)), Some(release_frame)) => {
+                            // SHM ownership moves to V8; SHM_FREE is sent on collection
+                            PromiseRegistry::resolve_binary_shared(id, shm, tag.as_ref(), release_frame);
+                        }
+                        (Ok(payload), _) => {
+                            debug!("[Renderer] binary response: {} bytes", payload.as_slice().len());
 
-                        PromiseRegistry::resolve_binary(id, &buf);
-                    } else {
-                        // Browser used SHM for this response
-                        let name: CefString = (&args.string(2)).into();
-                        let name = name.to_string();
-                        let size = list_int(&args, 3) as usize;
+                            // V8 copies the data during resolve; SHM must remain valid until then
+                            PromiseRegistry::resolve_binary(id, payload.as_slice(), tag.as_ref());
 
-                        // Pass SHM slice directly; V8 performs the copy internally
-                        // V8 copies the data during resolve; SHM must remain valid until then
-                        let shm = match SharedBuffer::open(&name, size) {
-                            Ok(s) => s,
-                            Err(e) => {
-                                eprintln!("[IPC] SHM open failed for id={}: {}", id, e);
-                                let msg = CefString::from(format!("shm transport error: {}", e).as_str());
-                                PromiseRegistry::resolve_cef_string(id, false, &msg);
+                            // Notify browser it can release the SHM buffer
+                            if payload.is_shared() {
                                 if let Some(f) = frame { send_shm_free(id, f); }
-                                return 1;
                             }
-                        };
+                        }
+                        (Err(e), _) => {
+                            eprintln!("[IPC] SHM open failed for id={}: {}", id, e);
+                            let msg = CefString::from(format!("shm transport error: {}", e).as_str());
+                            PromiseRegistry::resolve_cef_string(id, false, &msg);
+                            if let Some(f) = frame { send_shm_free(id, f); }
+                        }
+                    }
+                }
+
+                IpcMessage::ResolveAttachments { id, meta, attachments } => {
+                    outgoing_shm().lock().unwrap().remove(&id);
+
+                    let used_shm = attachments.iter().any(|b| matches!(b, Blob::Shared { .. }));
 
-                        PromiseRegistry::resolve_binary(id, shm.as_slice());
+                    let peer = response_peer(id);
+                    let attachments: Result<Vec<Payload>, String> =
+                        attachments.into_iter().map(|blob| blob.open(peer)).collect();
 
-                        // Notify browser it can release the SHM buffer
-                        if let Some(f) = frame {
-                            send_shm_free(id, f);
+                    match attachments {
+                        Ok(attachments) => {
+                            debug!("[Renderer] attachment response: {} attachments", attachments.len());
+
+                            // V8 copies each attachment; SHM must remain mapped until then
+                            PromiseRegistry::resolve_attachments(id, &CefString::from(meta.as_str()), &attachments);
+                        }
+                        Err(e) => {
+                            eprintln!("[IPC] attachment response failed for id={}: {}", id, e);
+                            let msg = CefString::from(format!("attachment transport error: {}", e).as_str());
+                            PromiseRegistry::resolve_cef_string(id, false, &msg);
                         }
                     }
+
+                    if used_shm {
+                        if let Some(f) = frame { send_shm_free(id, f); }
+                    }
                 }
 
-                _ => {
-                    eprintln!("[IPC ERROR] unexpected message type {} from browser", msg_type);
+                IpcMessage::HelloAck { protocol, version, shm_prefix } => {
+                    match check_peer(protocol, &version) {
+                        Some(mismatch) => {
+                            eprintln!("[IPC ERROR] {}", mismatch);
+                            *protocol_error().lock().unwrap() = Some(mismatch);
+                        }
+                        None => {
+                            debug!("[Renderer] handshake ok: protocol v{}, browser kurogane {}", protocol, version);
+                        }
+                    }
+
+                    // One browser process per renderer; later contexts must see the same prefix
+                    let announced = BROWSER_SHM_PREFIX.get_or_init(|| shm_prefix.clone());
+                    if *announced != shm_prefix {
+                        eprintln!("[IPC ERROR] browser announced a different shm prefix, keeping the first");
+                    }
+                }
+
+                other => {
+                    eprintln!("[IPC ERROR] unexpected {:?} message from browser", other.message_type());
                 }
             }
 
@@ -328,6 +638,11 @@ wrap_v8_handler! {
             retval: Option<&mut Option<V8Value>>,
             exception: Option<&mut CefString>,
         ) -> i32 {
+            if let Some(err) = check_protocol() {
+                if let Some(exc) = exception { *exc = CefString::from(err.as_str()); }
+                return 0;
+            }
+
             // args must be present
             let args = match arguments {
                 Some(a) if !a.is_empty() => a,
@@ -363,6 +678,11 @@ wrap_v8_handler! {
                 _ => String::new(),
             };
 
+            if let Err(e) = limits().check_request(&cmd, payload.len()) {
+                if let Some(exc) = exception { *exc = CefString::from(e.as_str()); }
+                return 0;
+            }
+
             let context = match v8_context_get_current_context() {
                 Some(ctx) => ctx,
                 None => {
@@ -380,15 +700,120 @@ wrap_v8_handler! {
 
             // Use the captured frame
             if let Some(frame) = get_frame() {
-                let mut msg = process_message_create(Some(&CefString::from("ipc"))).unwrap();
-                let msg_args = msg.argument_list().unwrap();
+                match outgoing_text(id, &payload) {
+                    Ok(payload) => {
+                        IpcMessage::Invoke { id, command: cmd, payload }.send(&frame, ProcessId::BROWSER);
+                    }
+                    Err(e) => fail_call(id, &e),
+                }
+            }
+
+            if let Some(ret) = retval {
+                *ret = Some(promise);
+            }
 
-                msg_args.set_int(0, 0);
-                msg_args.set_int(1, id as i32);
-                msg_args.set_string(2, Some(&CefString::from(cmd.as_str())));
-                msg_args.set_string(3, Some(&CefString::from(payload.as_str())));
+            1
+        }
+    }
+}
+
+//
+// Zero-copy statistics: { delivered, copied, supported }
+//
+
+wrap_v8_handler! {
+    pub struct ZeroCopyStatsHandler;
 
-                frame.send_process_message(ProcessId::BROWSER, Some(&mut msg));
+    impl V8Handler {
+        fn execute(
+            &self,
+            _name: Option<&CefString>,
+            _object: Option<&mut V8Value>,
+            _arguments: Option<&[Option<V8Value>]>,
+            retval: Option<&mut Option<V8Value>>,
+            _exception: Option<&mut CefString>,
+        ) -> i32 {
+            let stats = v8_value_create_object(None, None).unwrap();
+
+            let counters = [
+                ("delivered", ZERO_COPY_DELIVERED.load(Ordering::Relaxed)),
+                ("copied", ZERO_COPY_COPIED.load(Ordering::Relaxed)),
+            ];
+            for (key, count) in counters {
+                let mut v = v8_value_create_double(count as f64).unwrap();
+                stats.set_value_bykey(Some(&CefString::from(key)), Some(&mut v), V8Propertyattribute::default());
+            }
+
+            let mut supported = v8_value_create_bool(!EXTERNAL_BUFFERS_REJECTED.load(Ordering::Relaxed) as i32).unwrap();
+            stats.set_value_bykey(Some(&CefString::from("supported")), Some(&mut supported), V8Propertyattribute::default());
+
+            if let Some(ret) = retval {
+                *ret = Some(stats);
+            }
+
+            1
+        }
+    }
+}
+
+//
+// Batched JSON invoke handler
+//
+
+wrap_v8_handler! {
+    pub struct IpcInvokeBatchHandler;
+
+    impl V8Handler {
+        fn execute(
+            &self,
+            _name: Option<&CefString>,
+            _object: Option<&mut V8Value>,
+            arguments: Option<&[Option<V8Value>]>,
+            retval: Option<&mut Option<V8Value>>,
+            exception: Option<&mut CefString>,
+        ) -> i32 {
+            if let Some(err) = check_protocol() {
+                if let Some(exc) = exception { *exc = CefString::from(err.as_str()); }
+                return 0;
+            }
+
+            // Single argument: JSON encoded [[command, payload], ...] built by the bridge
+            let batch = match arguments.and_then(|a| a.first()) {
+                Some(Some(v)) if v.is_string() != 0 => v8_to_string(v),
+                _ => {
+                    if let Some(exc) = exception {
+                        *exc = CefString::from("invokeBatch requires a JSON encoded call list (use kurogane.batch())");
+                    }
+                    return 0;
+                }
+            };
+
+            // Per-call limits are enforced by the browser once the batch is decoded
+            if let Err(e) = limits().check_batch(batch.len()) {
+                if let Some(exc) = exception { *exc = CefString::from(e.as_str()); }
+                return 0;
+            }
+
+            let context = match v8_context_get_current_context() {
+                Some(ctx) => ctx,
+                None => {
+                    if let Some(exc) = exception {
+                        *exc = CefString::from("invokeBatch: no active renderer context");
+                    }
+                    return 0;
+                }
+            };
+            let promise = v8_value_create_promise().unwrap();
+
+            let id = register_promise(context.clone(), promise.clone());
+
+            debug!("[Renderer] JS batch invoke (id={})", id);
+
+            if let Some(frame) = get_frame() {
+                match outgoing_text(id, &batch) {
+                    Ok(calls) => IpcMessage::InvokeBatch { id, calls }.send(&frame, ProcessId::BROWSER),
+                    Err(e) => fail_call(id, &e),
+                }
             }
 
             if let Some(ret) = retval {
@@ -417,6 +842,10 @@ wrap_v8_handler! {
             retval: Option<&mut Option<V8Value>>,
             exception: Option<&mut CefString>,
         ) -> i32 {
+            if let Some(err) = check_protocol() {
+                if let Some(exc) = exception { *exc = CefString::from(err.as_str()); }
+                return 0;
+            }
 
             let args = match arguments {
                 Some(a) if a.len() >= 2 => a,
@@ -450,14 +879,22 @@ wrap_v8_handler! {
                 }
             };
 
-            let ptr = buffer.array_buffer_data();
-            let len = buffer.array_buffer_byte_length();
+            // Optional third argument: deliver a SHM response without copying it
+            let zero_copy = matches!(
+                args.get(2),
+                Some(Some(v)) if v.is_bool() != 0 && v.bool_value() != 0
+            );
 
-            if ptr.is_null() {
+            let Some((ptr, len)) = array_buffer_parts(buffer) else {
                 if let Some(exc) = exception {
                     *exc = CefString::from("ArrayBuffer has null data");
                 }
                 return 0;
+            };
+
+            if let Err(e) = limits().check_request(&cmd, len) {
+                if let Some(exc) = exception { *exc = CefString::from(e.as_str()); }
+                return 0;
             }
 
             let context = match v8_context_get_current_context() {
@@ -473,33 +910,167 @@ wrap_v8_handler! {
 
             let id = register_promise(context.clone(), promise.clone());
 
-            let mut msg = process_message_create(Some(&CefString::from("ipc"))).unwrap();
-            let msg_args = msg.argument_list().unwrap();
+            if zero_copy {
+                zero_copy_calls().lock().unwrap().insert(id);
+            }
 
-            msg_args.set_int(0, 3);
-            msg_args.set_int(1, id as i32);
-            msg_args.set_string(2, Some(&CefString::from(cmd.as_str())));
+            // Inline for small-medium sizes, SHM only for large payloads
+            let msg = with_array_buffer(ptr, len, |data| {
+                let (data, shm) = Blob::outgoing(data)?;
 
-            with_array_buffer(ptr as *const u8, len, |data| {
-                if len < SHM_THRESHOLD {
-                    // inline: faster for small-medium sizes
-                    let mut binary = binary_value_create(Some(data)).unwrap();
-                    msg_args.set_binary(3, Some(&mut binary));
-                } else {
-                    // shm: only for large payloads
-                    let mut shm = SharedBuffer::create(len);
-                    shm.write(data);
+                if let Some(shm) = shm {
+                    outgoing_shm().lock().unwrap().insert(id, vec![shm]);
+                }
 
-                    let name = shm.name();
-                    msg_args.set_string(3, Some(&CefString::from(name.as_str())));
-                    msg_args.set_int(4, len as i32);
+                Ok::<_, String>(IpcMessage::InvokeBinary { id, command: cmd, data }.encode())
+            });
 
-                    outgoing_shm().lock().unwrap().insert(id, shm);
+            match msg {
+                Ok(mut msg) => {
+                    if let Some(frame) = get_frame() {
+                        frame.send_process_message(ProcessId::BROWSER, Some(&mut msg));
+                    }
                 }
-            });
+                Err(e) => fail_call(id, &e),
+            }
 
-            if let Some(frame) = get_frame() {
-                frame.send_process_message(ProcessId::BROWSER, Some(&mut msg));
+            if let Some(ret) = retval {
+                *ret = Some(promise);
+            }
+
+            1
+        }
+    }
+}
+
+//
+// Attachment invoke handler
+//
+
+wrap_v8_handler! {
+    pub struct IpcInvokeAttachmentsHandler;
+
+    impl V8Handler {
+
+        fn execute(
+            &self,
+            _name: Option<&CefString>,
+            _object: Option<&mut V8Value>,
+            arguments: Option<&[Option<V8Value>]>,
+            retval: Option<&mut Option<V8Value>>,
+            exception: Option<&mut CefString>,
+        ) -> i32 {
+            if let Some(err) = check_protocol() {
+                if let Some(exc) = exception { *exc = CefString::from(err.as_str()); }
+                return 0;
+            }
+
+            let args = match arguments {
+                Some(a) if a.len() >= 3 => a,
+                _ => {
+                    if let Some(exc) = exception {
+                        *exc = CefString::from("invokeAttachments(command, metaJson, ArrayBuffer[])");
+                    }
+                    return 0;
+                }
+            };
+
+            let cmd = match args.get(0) {
+                Some(Some(v)) if v.is_string() != 0 => v8_to_string(v),
+                _ => {
+                    if let Some(exc) = exception { *exc = CefString::from("command must be a string"); }
+                    return 0;
+                }
+            };
+
+            let meta = match args.get(1) {
+                Some(Some(v)) if v.is_string() != 0 => v8_to_string(v),
+                _ => String::new(),
+            };
+
+            let list = match args.get(2) {
+                Some(Some(v)) if v.is_array() != 0 => v,
+                _ => {
+                    if let Some(exc) = exception {
+                        *exc = CefString::from("attachments must be an array of ArrayBuffers (use invoke())");
+                    }
+                    return 0;
+                }
+            };
+
+            // Collect backing stores up front; every element must be an ArrayBuffer
+            let mut views: Vec<(*const u8, usize)> = Vec::new();
+            for i in 0..list.array_length() {
+                let view = list
+                    .value_byindex(i)
+                    .filter(|v| v.is_array_buffer() != 0)
+                    .and_then(|v| array_buffer_parts(&v));
+
+                match view {
+                    Some(view) => views.push(view),
+                    None => {
+                        if let Some(exc) = exception {
+                            *exc = CefString::from(format!("attachment {} is not an ArrayBuffer", i).as_str());
+                        }
+                        return 0;
+                    }
+                }
+            }
+
+            let size = meta.len() + views.iter().map(|&(_, len)| len).sum::<usize>();
+            if let Err(e) = limits().check_request(&cmd, size) {
+                if let Some(exc) = exception { *exc = CefString::from(e.as_str()); }
+                return 0;
+            }
+
+            let context = match v8_context_get_current_context() {
+                Some(ctx) => ctx,
+                None => {
+                    if let Some(exc) = exception {
+                        *exc = CefString::from("invokeAttachments: no active renderer context");
+                    }
+                    return 0;
+                }
+            };
+            let promise = v8_value_create_promise().unwrap();
+
+            let id = register_promise(context.clone(), promise.clone());
+
+            // SAFETY: same guarantees as with_array_buffer(); the slices are only
+            // used synchronously below while V8 keeps the backing stores alive.
+            let slices: Vec<&[u8]> = views
+                .iter()
+                .map(|&(ptr, len)| unsafe { std::slice::from_raw_parts(ptr, len) })
+                .collect();
+
+            debug!(
+                "[Renderer] JS attachment invoke: '{}' (id={}, {} attachments)",
+                cmd, id, slices.len()
+            );
+
+            let mut shms = Vec::new();
+            let attachments = slices
+                .iter()
+                .map(|data| {
+                    let (blob, shm) = Blob::outgoing(data)?;
+                    shms.extend(shm);
+                    Ok(blob)
+                })
+                .collect::<Result<Vec<_>, String>>();
+
+            match attachments {
+                Ok(attachments) => {
+                    let mut msg = IpcMessage::InvokeAttachments { id, command: cmd, meta, attachments }.encode();
+
+                    if !shms.is_empty() {
+                        outgoing_shm().lock().unwrap().insert(id, shms);
+                    }
+
+                    if let Some(frame) = get_frame() {
+                        frame.send_process_message(ProcessId::BROWSER, Some(&mut msg));
+                    }
+                }
+                Err(e) => fail_call(id, &e),
             }
 
             if let Some(ret) = retval {
@@ -511,6 +1082,18 @@ wrap_v8_handler! {
     }
 }
 
+/// Pointer and length of an ArrayBuffer's bytes. An empty buffer may have
+/// no backing store, so null data is only an error when there are bytes.
+fn array_buffer_parts(buffer: &V8Value) -> Option<(*const u8, usize)> {
+    let len = buffer.array_buffer_byte_length();
+    if len == 0 {
+        return Some((std::ptr::NonNull::dangling().as_ptr(), 0));
+    }
+
+    let ptr = buffer.array_buffer_data() as *const u8;
+    (!ptr.is_null()).then_some((ptr, len))
+}
+
 #[inline(always)]
 fn with_array_buffer<R>(
     ptr: *const u8,
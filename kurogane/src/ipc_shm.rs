@@ -1,13 +1,134 @@
 //! Shared memory helper for binary IPC.
 
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
 use shared_memory::{Shmem, ShmemConf};
 
+use crate::ipc_limits::limits;
+
 // Empirically derived crossover point (~2.5-3MB) where SHM becomes faster than inline
 pub const SHM_THRESHOLD: usize = 3 * 1024 * 1024; // 3MB
 
+/// Prefix of every region created by SharedBuffer::create.
+/// Names received over IPC must carry it, so a peer cannot make us map
+/// shared memory that belongs to another application.
+pub const SHM_PREFIX: &str = "k_";
+
+// Announced prefixes are "k_<nonce>_", the nonce 8 hex digits
+const NONCE_LEN: usize = 8;
+const PREFIX_LEN: usize = SHM_PREFIX.len() + NONCE_LEN + 1;
+
+// macOS rejects names over 31 bytes (PSHMNAMLEN), counting the leading '/'
+// shared_memory adds. With a full 16-digit counter names stay at 28.
+const MAX_NAME_LEN: usize = 30 - 1;
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+// Bytes held by regions this process created and has not dropped yet
+static OUTSTANDING: AtomicUsize = AtomicUsize::new(0);
+
+/// Reserve `size` bytes of the outstanding SHM quota.
+fn reserve(size: usize) -> Result<(), String> {
+    let max = limits().outstanding_shm();
+
+    OUTSTANDING
+        .fetch_update(Ordering::AcqRel, Ordering::Acquire, |held| {
+            held.checked_add(size).filter(|total| *total <= max)
+        })
+        .map(|_| ())
+        .map_err(|held| {
+            format!(
+                "shared memory quota exceeded: {} bytes requested, {} of {} bytes already held by calls in flight",
+                size, held, max
+            )
+        })
+}
+
+/// Prefix of the regions this process creates: `k_<nonce>_`.
+///
+/// The nonce is random and mixes in the pid, so names of one process can't
+/// be guessed by another, and a crashed process with a recycled pid does
+/// not collide. It is short because macOS limits names to 31 bytes. Each
+/// side announces its prefix in the handshake and only maps regions
+/// carrying the prefix its peer announced.
+pub fn own_prefix() -> &'static str {
+    static PREFIX: OnceLock<String> = OnceLock::new();
+
+    PREFIX.get_or_init(|| {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u32(std::process::id());
+        hasher.write_u128(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_nanos())
+                .unwrap_or(0),
+        );
+
+        let hash = hasher.finish();
+        format!("{}{:08x}_", SHM_PREFIX, (hash ^ (hash >> 32)) as u32)
+    })
+}
+
+/// Check that `name` looks like a region produced by SharedBuffer::create.
+pub fn validate_name(name: &str) -> Result<(), String> {
+    let valid = name.len() <= MAX_NAME_LEN
+        && name
+            .strip_prefix(SHM_PREFIX)
+            .is_some_and(|rest| !rest.is_empty() && rest.bytes().all(|b| b.is_ascii_hexdigit() || b == b'_'));
+
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("invalid shm name '{}'", name.escape_debug()))
+    }
+}
+
+/// Check a prefix announced in the handshake: `k_<8 hex digits>_`, and
+/// not the one this process uses itself.
+pub fn validate_prefix(prefix: &str) -> Result<(), String> {
+    let valid = prefix
+        .strip_prefix(SHM_PREFIX)
+        .and_then(|rest| rest.strip_suffix('_'))
+        .is_some_and(|nonce| nonce.len() == NONCE_LEN && nonce.bytes().all(|b| b.is_ascii_hexdigit()));
+
+    if !valid {
+        return Err(format!("invalid shm prefix '{}'", prefix.escape_debug()));
+    }
+
+    if prefix == own_prefix() {
+        return Err("peer announced this process's own shm prefix".to_string());
+    }
+
+    Ok(())
+}
+
+/// Check that `name` was created by the peer that announced `peer`.
+pub fn check_peer_name(name: &str, peer: Option<&str>) -> Result<(), String> {
+    validate_name(name)?;
+
+    let peer = peer.ok_or_else(|| {
+        format!("shm '{}' has no announcing peer (no handshake, or no call in flight)", name.escape_debug())
+    })?;
+
+    let counter = name.strip_prefix(peer).unwrap_or_default();
+
+    // A u64 counter in hex, as create() writes it
+    if !(1..=16).contains(&counter.len()) || !counter.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(format!("shm '{}' was not created by the peer", name.escape_debug()));
+    }
+
+    Ok(())
+}
+
 pub struct SharedBuffer {
     shmem: Shmem,
     size: usize,
+    // Created here, so counted against the outstanding quota
+    owned: bool,
 }
 
 // SAFETY: On Windows, the shared_memory crate exposes a raw OS handle
@@ -20,23 +141,42 @@ unsafe impl Send for SharedBuffer {}
 
 impl SharedBuffer {
 
-    /// Create a new shared memory region.
-    pub fn create(size: usize) -> Self {
-        let shmem = ShmemConf::new()
+    /// Create a new shared memory region named `<own_prefix><n>`.
+    pub fn create(size: usize) -> Result<Self, String> {
+        let name = format!("{}{:x}", own_prefix(), NEXT_ID.fetch_add(1, Ordering::Relaxed));
+
+        reserve(size)?;
+
+        ShmemConf::new()
             .size(size)
+            .os_id(&name)
             .create()
-            .expect("failed to create shared memory");
-
-        Self { shmem, size }
+            .map(|shmem| Self { shmem, size, owned: true })
+            .map_err(|e| {
+                OUTSTANDING.fetch_sub(size, Ordering::AcqRel);
+                format!("shm create '{}' ({} bytes): {}", name, size, e)
+            })
     }
 
-    /// Open an existing shared memory region.
-    pub fn open(name: &str, size: usize) -> Result<Self, String> {
-        ShmemConf::new()
+    /// Open an existing shared memory region of at least `size` bytes.
+    /// Only names created by the peer that announced `peer` are accepted.
+    pub fn open(name: &str, size: usize, peer: Option<&str>) -> Result<Self, String> {
+        check_peer_name(name, peer)?;
+
+        let shmem = ShmemConf::new()
             .os_id(name)
             .open()
-            .map(|shmem| Self { shmem, size })
-            .map_err(|e| format!("shm open '{}': {}", name, e))
+            .map_err(|e| format!("shm open '{}': {}", name, e))?;
+
+        // The declared size comes from the peer; never map past the real region
+        if shmem.len() < size {
+            return Err(format!(
+                "shm '{}' is {} bytes but {} were declared",
+                name, shmem.len(), size
+            ));
+        }
+
+        Ok(Self { shmem, size, owned: false })
     }
 
     /// OS identifier used for cross-process sharing.
@@ -44,6 +184,16 @@ impl SharedBuffer {
         self.shmem.get_os_id().to_string()
     }
 
+    /// Size of the region in bytes.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Raw pointer to the start of the mapping.
+    pub fn as_ptr(&self) -> *mut u8 {
+        self.shmem.as_ptr()
+    }
+
     /// Immutable view of the memory.
     pub fn as_slice(&self) -> &[u8] {
         unsafe { std::slice::from_raw_parts(self.shmem.as_ptr(), self.size) }
@@ -59,3 +209,97 @@ impl SharedBuffer {
         self.as_slice_mut()[..data.len()].copy_from_slice(data);
     }
 }
+
+impl Drop for SharedBuffer {
+    fn drop(&mut self) {
+        if self.owned {
+            OUTSTANDING.fetch_sub(self.size, Ordering::AcqRel);
+        }
+    }
+}
+
+/// Bytes received over IPC, either copied out of a BinaryValue or still mapped in SHM.
+pub enum Payload {
+    Inline(Vec<u8>),
+    Shared(SharedBuffer),
+}
+
+impl Payload {
+    pub fn as_slice(&self) -> &[u8] {
+        match self {
+            Payload::Inline(v) => v,
+            Payload::Shared(shm) => shm.as_slice(),
+        }
+    }
+
+    pub fn is_shared(&self) -> bool {
+        matches!(self, Payload::Shared(_))
+    }
+
+    pub fn into_vec(self) -> Vec<u8> {
+        match self {
+            Payload::Inline(v) => v,
+            Payload::Shared(shm) => shm.as_slice().to_vec(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_fit_macos_limit() {
+        let own = own_prefix();
+        assert_eq!(own.len(), PREFIX_LEN);
+
+        // Valid in shape, refused only because it is ours
+        let err = validate_prefix(own).unwrap_err();
+        assert!(err.contains("own"), "{}", err);
+
+        // As create() names them, with the largest counter, plus the '/'
+        // shared_memory prepends: PSHMNAMLEN is 31, leave room for the NUL
+        let name = format!("{}{:x}", own, u64::MAX);
+        assert!(format!("/{}", name).len() <= 30, "{} is too long for macOS", name);
+        assert!(validate_name(&name).is_ok());
+    }
+
+    #[test]
+    fn validates_prefixes() {
+        assert!(validate_prefix("k_0123abcd_").is_ok());
+
+        for bad in [
+            "",
+            "k_",
+            "k__",
+            "k_0123abc_",
+            "k_0123abcde_",
+            "k_0123abcd",
+            "k_0123abcg_",
+            "k_0123_abcd_",
+            "x_0123abcd_",
+            "k_0123abcd_/",
+        ] {
+            assert!(validate_prefix(bad).is_err(), "{:?} accepted", bad);
+        }
+    }
+
+    #[test]
+    fn accepts_only_the_peers_regions() {
+        let peer = Some("k_0000001f_");
+
+        assert!(check_peer_name("k_0000001f_0", peer).is_ok());
+        assert!(check_peer_name("k_0000001f_ffffffffffffffff", peer).is_ok());
+
+        // Someone else's region, or a name that only extends the prefix
+        assert!(check_peer_name("k_0000002f_0", peer).is_err());
+        assert!(check_peer_name("k_0000001f_", peer).is_err());
+        assert!(check_peer_name("k_0000001f_0_1", peer).is_err());
+        assert!(check_peer_name("k_0000001f_0ffffffffffffffff", peer).is_err());
+
+        // Our own regions, or anything before the handshake
+        let own = format!("{}0", own_prefix());
+        assert!(check_peer_name(&own, peer).is_err());
+        assert!(check_peer_name("k_0000001f_0", None).is_err());
+    }
+}
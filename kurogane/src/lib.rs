@@ -13,12 +13,23 @@ mod cef_app;
 mod browser;
 mod window;
 mod client;
+mod load_error;
+mod intercept;
+mod har;
 mod scheme;
 mod error;
 mod ipc_renderer;
 mod ipc_browser;
 mod ipc_shm;
+mod ipc_protocol;
+mod ipc_limits;
+mod typed_buffer;
+mod embedded;
+mod mime;
+mod pack;
+mod archive;
 pub mod logger;
+pub mod metrics;
 
 #[cfg(target_os = "macos")]
 mod platform;
@@ -26,6 +37,19 @@ mod platform;
 pub use runtime::Runtime;
 pub use error::RuntimeError;
 pub use app::App;
+pub use typed_buffer::{DType, TypedBuffer};
+pub use ipc_limits::{IpcLimits, CommandLimits};
+pub use embedded::{EmbeddedAssets, EmbeddedFile};
+pub use archive::Archive;
+pub use scheme::route::{SchemeRequest, SchemeResponse};
+pub use scheme::mount::{Mount, SchemeFlags};
+pub use intercept::{Action, InterceptedRequest};
+pub use kurogane_macros::include_assets;
 
 // Re-export IPC types for public use
-pub use ipc_browser::{IpcResult, register_command, register_binary_command};
+pub use ipc_browser::{
+    IpcResult,
+    register_command,
+    register_binary_command,
+    register_attachment_command,
+};
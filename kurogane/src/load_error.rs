@@ -0,0 +1,140 @@
+//! Built-in page shown when a navigation fails, e.g. the dev server is down
+//! or a remote URL can't be reached.
+//!
+//! Loaded as a data: URL into the failed frame, so it needs no scheme or
+//! assets of its own.
+
+// net::ERR_ABORTED: the navigation was cancelled or replaced, not failed
+const ERR_ABORTED: i32 = -3;
+
+const TEMPLATE: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>Page failed to load</title>
+<style>
+  html { color-scheme: dark light; }
+  body { margin: 0; min-height: 100vh; display: grid; place-items: center;
+         font: 15px/1.5 system-ui, sans-serif; background: #0f172a; color: #e2e8f0; }
+  main { max-width: 560px; padding: 32px; }
+  h1 { font-size: 22px; margin: 0 0 8px; }
+  p { margin: 0 0 16px; color: #94a3b8; }
+  code { display: block; padding: 8px 12px; border-radius: 6px; background: #1e293b;
+         color: #e2e8f0; word-break: break-all; }
+  .code { color: #f87171; }
+  button { font: inherit; padding: 8px 20px; border: 0; border-radius: 6px;
+           background: #3b82f6; color: white; cursor: pointer; }
+  button:hover { background: #2563eb; }
+</style>
+</head>
+<body>
+<main>
+  <h1>This page failed to load</h1>
+  <p class="code">{{text}} ({{code}})</p>
+  <p><code>{{url}}</code></p>
+  <button data-url="{{url}}" onclick="location.replace(this.dataset.url)">Retry</button>
+</main>
+</body>
+</html>
+"#;
+
+/// data: URL of the error page for a failed navigation, or None when the
+/// navigation was merely cancelled.
+pub(crate) fn page_url(url: &str, code: i32, text: &str) -> Option<String> {
+    if code == ERR_ABORTED {
+        return None;
+    }
+
+    let text = if text.is_empty() { "Unknown error" } else { text };
+
+    let html = TEMPLATE
+        .replace("{{text}}", &escape(text))
+        .replace("{{code}}", &code.to_string())
+        .replace("{{url}}", &escape(url));
+
+    Some(format!("data:text/html;charset=utf-8,{}", percent_encode(&html)))
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+fn percent_encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || b"-_.~".contains(&b) {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{:02X}", b));
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn html(page: &str) -> String {
+        let encoded = page.strip_prefix("data:text/html;charset=utf-8,").unwrap();
+        let bytes = encoded.as_bytes();
+        let mut out = Vec::new();
+        let mut i = 0;
+
+        while i < bytes.len() {
+            if bytes[i] == b'%' {
+                out.push(u8::from_str_radix(&encoded[i + 1..i + 3], 16).unwrap());
+                i += 3;
+            } else {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn aborted_navigations_keep_the_page() {
+        assert_eq!(page_url("https://example.com/", ERR_ABORTED, "net::ERR_ABORTED"), None);
+    }
+
+    #[test]
+    fn describes_the_failure() {
+        let page = html(&page_url("http://localhost:5173/", -102, "net::ERR_CONNECTION_REFUSED").unwrap());
+
+        assert!(page.contains("net::ERR_CONNECTION_REFUSED (-102)"));
+        assert!(page.contains("<code>http://localhost:5173/</code>"));
+        assert!(page.contains(r#"data-url="http://localhost:5173/""#));
+
+        let page = html(&page_url("https://example.com/", -105, "").unwrap());
+        assert!(page.contains("Unknown error (-105)"));
+    }
+
+    #[test]
+    fn escapes_the_url_and_text() {
+        let url = r#"https://example.com/?q="><script>alert('x')</script>"#;
+        let page = html(&page_url(url, -105, "<b>'bad'</b>").unwrap());
+        let escaped = "https://example.com/?q=&quot;&gt;&lt;script&gt;alert(&#39;x&#39;)&lt;/script&gt;";
+
+        assert!(page.contains(&format!("<code>{}</code>", escaped)));
+        assert!(page.contains(&format!(r#"data-url="{}""#, escaped)));
+        assert!(page.contains("&lt;b&gt;&#39;bad&#39;&lt;/b&gt; (-105)"));
+        assert!(!page.contains("<script>"));
+        assert!(!page.contains("'x'"));
+    }
+
+    #[test]
+    fn percent_encodes_everything_but_unreserved() {
+        let page = page_url("https://example.com/#a b", -105, "x").unwrap();
+        let body = page.strip_prefix("data:text/html;charset=utf-8,").unwrap();
+
+        assert!(body.bytes().all(|b| b.is_ascii_alphanumeric() || b"-_.~%".contains(&b)));
+    }
+}
@@ -0,0 +1,259 @@
+//! IPC metrics collected in the browser process.
+//!
+//! Every dispatched call is counted per command and transport, with
+//! histograms of request size, response size and handler latency.
+//! Read them with `kurogane::metrics::snapshot()`, or expose them to the
+//! frontend with App::metrics_command.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use serde::Serialize;
+
+/// Size bucket upper bounds in bytes: 1 KB to 1 GB in powers of four.
+const SIZE_BOUNDS: &[u64] = &[
+    1 << 10, 1 << 12, 1 << 14, 1 << 16, 1 << 18,
+    1 << 20, 1 << 22, 1 << 24, 1 << 26, 1 << 28, 1 << 30,
+];
+
+/// Latency bucket upper bounds in microseconds: 50 µs to 1 s.
+const LATENCY_BOUNDS: &[u64] = &[
+    50, 100, 250, 500,
+    1_000, 2_500, 5_000, 10_000, 25_000, 50_000,
+    100_000, 250_000, 500_000, 1_000_000,
+];
+
+// Commands are named by the page; fold anything past this into OTHER
+const MAX_COMMANDS: usize = 1024;
+const OTHER: &str = "<other>";
+
+static METRICS: OnceLock<Mutex<HashMap<(String, Transport), CommandMetrics>>> = OnceLock::new();
+
+fn metrics() -> &'static Mutex<HashMap<(String, Transport), CommandMetrics>> {
+    METRICS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// How a call's payloads crossed the process boundary.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+    /// Request and response were both carried inline in the process message.
+    Inline,
+    /// The request or the response went through shared memory.
+    Shm,
+}
+
+/// Fixed-bucket histogram. `counts[i]` holds values `<= bounds[i]`;
+/// the last count holds everything above the largest bound.
+#[derive(Clone, Debug, Serialize)]
+pub struct Histogram {
+    pub bounds: &'static [u64],
+    pub counts: Vec<u64>,
+    pub count: u64,
+    pub sum: u64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [u64]) -> Self {
+        Self { bounds, counts: vec![0; bounds.len() + 1], count: 0, sum: 0 }
+    }
+
+    fn record(&mut self, value: u64) {
+        let bucket = self.bounds.partition_point(|b| *b < value);
+        self.counts[bucket] += 1;
+        self.count += 1;
+        self.sum = self.sum.saturating_add(value);
+    }
+
+    /// Upper bound of the bucket containing quantile `q` (0.0..=1.0).
+    /// None when empty or when the quantile falls in the overflow bucket.
+    pub fn quantile(&self, q: f64) -> Option<u64> {
+        if self.count == 0 {
+            return None;
+        }
+
+        let rank = ((q.clamp(0.0, 1.0) * self.count as f64).ceil() as u64).max(1);
+
+        let mut seen = 0;
+        for (i, c) in self.counts.iter().enumerate() {
+            seen += c;
+            if seen >= rank {
+                return self.bounds.get(i).copied();
+            }
+        }
+
+        None
+    }
+}
+
+/// Counters and histograms for one command over one transport.
+#[derive(Clone, Debug, Serialize)]
+pub struct CommandMetrics {
+    pub command: String,
+    pub transport: Transport,
+    pub calls: u64,
+    pub errors: u64,
+    pub request_bytes: Histogram,
+    pub response_bytes: Histogram,
+    pub latency_us: Histogram,
+}
+
+impl CommandMetrics {
+    fn new(command: String, transport: Transport) -> Self {
+        Self {
+            command,
+            transport,
+            calls: 0,
+            errors: 0,
+            request_bytes: Histogram::new(SIZE_BOUNDS),
+            response_bytes: Histogram::new(SIZE_BOUNDS),
+            latency_us: Histogram::new(LATENCY_BOUNDS),
+        }
+    }
+}
+
+/// Point-in-time copy of all IPC metrics, sorted by command then transport.
+#[derive(Clone, Debug, Serialize)]
+pub struct Snapshot {
+    pub commands: Vec<CommandMetrics>,
+}
+
+/// One dispatched call, as seen by the browser process.
+pub(crate) struct Call<'a> {
+    pub command: &'a str,
+    pub shm: bool,
+    pub request_bytes: usize,
+    /// Response size, or None if the call failed.
+    pub response_bytes: Option<usize>,
+    pub elapsed: Duration,
+}
+
+pub(crate) fn record(call: Call) {
+    let transport = if call.shm { Transport::Shm } else { Transport::Inline };
+
+    let mut map = metrics().lock().unwrap();
+
+    let mut command = call.command;
+    if map.len() >= MAX_COMMANDS && !map.contains_key(&(command.to_string(), transport)) {
+        command = OTHER;
+    }
+
+    let entry = map
+        .entry((command.to_string(), transport))
+        .or_insert_with(|| CommandMetrics::new(command.to_string(), transport));
+
+    entry.calls += 1;
+    entry.request_bytes.record(call.request_bytes as u64);
+    entry.latency_us.record(call.elapsed.as_micros().min(u64::MAX as u128) as u64);
+
+    match call.response_bytes {
+        Some(size) => entry.response_bytes.record(size as u64),
+        None => entry.errors += 1,
+    }
+}
+
+/// Copy the current metrics.
+pub fn snapshot() -> Snapshot {
+    let mut commands: Vec<CommandMetrics> = metrics().lock().unwrap().values().cloned().collect();
+    commands.sort_by(|a, b| (&a.command, a.transport as u8).cmp(&(&b.command, b.transport as u8)));
+
+    Snapshot { commands }
+}
+
+/// Clear all metrics, e.g. after a dashboard scrape.
+pub fn reset() {
+    metrics().lock().unwrap().clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(command: &str, shm: bool, response_bytes: Option<usize>) -> Call<'_> {
+        Call { command, shm, request_bytes: 10, response_bytes, elapsed: Duration::from_micros(75) }
+    }
+
+    #[test]
+    fn buckets_include_their_upper_bound() {
+        let mut h = Histogram::new(SIZE_BOUNDS);
+
+        for value in [0, 1024, 1025, 4096, 1 << 30, (1 << 30) + 1, u64::MAX] {
+            h.record(value);
+        }
+
+        assert_eq!(h.counts[0], 2);
+        assert_eq!(h.counts[1], 2);
+        assert_eq!(h.counts[SIZE_BOUNDS.len() - 1], 1);
+        assert_eq!(h.counts[SIZE_BOUNDS.len()], 2);
+        assert_eq!(h.count, 7);
+        assert_eq!(h.sum, u64::MAX);
+    }
+
+    #[test]
+    fn quantiles_report_bucket_bounds() {
+        let mut h = Histogram::new(LATENCY_BOUNDS);
+        assert_eq!(h.quantile(0.5), None);
+
+        // 90 fast calls, 9 slow ones and one past the largest bound
+        for _ in 0..90 {
+            h.record(40);
+        }
+        for _ in 0..9 {
+            h.record(3_000);
+        }
+        h.record(2_000_000);
+
+        assert_eq!(h.quantile(0.0), Some(50));
+        assert_eq!(h.quantile(0.5), Some(50));
+        assert_eq!(h.quantile(0.9), Some(50));
+        assert_eq!(h.quantile(0.91), Some(5_000));
+        assert_eq!(h.quantile(0.99), Some(5_000));
+        assert_eq!(h.quantile(1.0), None);
+        assert_eq!(h.quantile(-1.0), Some(50));
+    }
+
+    // One test owns the global map, so parallel tests can't race on it
+    #[test]
+    fn records_resets_and_caps_commands() {
+        reset();
+
+        record(call("load", false, Some(100)));
+        record(call("load", false, None));
+        record(call("load", true, Some(1 << 20)));
+
+        let snapshot = snapshot();
+        assert_eq!(snapshot.commands.len(), 2);
+
+        let inline = &snapshot.commands[0];
+        assert_eq!((inline.command.as_str(), inline.transport), ("load", Transport::Inline));
+        assert_eq!((inline.calls, inline.errors), (2, 1));
+        assert_eq!(inline.request_bytes.count, 2);
+        assert_eq!(inline.response_bytes.count, 1);
+        assert_eq!(inline.latency_us.quantile(0.5), Some(100));
+        assert_eq!(snapshot.commands[1].transport, Transport::Shm);
+
+        reset();
+        assert!(super::snapshot().commands.is_empty());
+
+        for i in 0..MAX_COMMANDS {
+            record(call(&format!("cmd{}", i), false, Some(1)));
+        }
+        record(call("extra", false, Some(1)));
+        record(call("another", true, Some(1)));
+        record(call("cmd0", false, Some(1)));
+
+        let commands = super::snapshot().commands;
+        let find = |name: &str, transport| {
+            commands.iter().find(|c| c.command == name && c.transport == transport)
+        };
+
+        assert!(find("extra", Transport::Inline).is_none());
+        assert_eq!(find(OTHER, Transport::Inline).unwrap().calls, 1);
+        assert_eq!(find(OTHER, Transport::Shm).unwrap().calls, 1);
+        assert_eq!(find("cmd0", Transport::Inline).unwrap().calls, 2);
+        assert_eq!(commands.len(), MAX_COMMANDS + 2);
+
+        reset();
+    }
+}
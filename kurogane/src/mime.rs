@@ -0,0 +1,144 @@
+//! MIME types by file extension.
+//!
+//! Also compiled into kurogane-macros to type embedded assets at build time,
+//! so this file must only depend on std.
+
+// Each crate uses a different subset
+#![allow(dead_code)]
+
+use std::path::Path;
+
+pub const DEFAULT: &str = "application/octet-stream";
+
+/// MIME type for an extension without the leading dot, case-insensitive.
+/// Text types carry `charset=utf-8`.
+pub fn from_extension(ext: &str) -> &'static str {
+    match ext.to_ascii_lowercase().as_str() {
+        // Documents
+        "html" | "htm" => "text/html; charset=utf-8",
+        "xhtml" => "application/xhtml+xml; charset=utf-8",
+        "txt" | "text" | "log" => "text/plain; charset=utf-8",
+        "md" | "markdown" => "text/markdown; charset=utf-8",
+        "csv" => "text/csv; charset=utf-8",
+        "tsv" => "text/tab-separated-values; charset=utf-8",
+        "xml" => "application/xml; charset=utf-8",
+        "pdf" => "application/pdf",
+        "rtf" => "application/rtf",
+
+        // Scripts, styles and data
+        "js" | "mjs" | "cjs" => "text/javascript; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "json" | "map" => "application/json; charset=utf-8",
+        "jsonld" => "application/ld+json; charset=utf-8",
+        "webmanifest" => "application/manifest+json; charset=utf-8",
+        "yaml" | "yml" => "application/yaml; charset=utf-8",
+        "toml" => "application/toml; charset=utf-8",
+        "wasm" => "application/wasm",
+        "wgsl" => "text/wgsl; charset=utf-8",
+        "glsl" | "vert" | "frag" => "text/plain; charset=utf-8",
+
+        // Images
+        "png" => "image/png",
+        "apng" => "image/apng",
+        "jpg" | "jpeg" | "jfif" | "pjpeg" | "pjp" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "svg" => "image/svg+xml",
+        "ico" | "cur" => "image/x-icon",
+        "bmp" => "image/bmp",
+        "tif" | "tiff" => "image/tiff",
+        "heic" => "image/heic",
+        "heif" => "image/heif",
+        "jxl" => "image/jxl",
+        "ktx" => "image/ktx",
+        "ktx2" => "image/ktx2",
+        "dds" => "image/vnd-ms.dds",
+        "hdr" => "image/vnd.radiance",
+        "exr" => "image/x-exr",
+
+        // Fonts
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "eot" => "application/vnd.ms-fontobject",
+
+        // Audio
+        "mp3" => "audio/mpeg",
+        "m4a" => "audio/mp4",
+        "aac" => "audio/aac",
+        "oga" | "ogg" | "opus" => "audio/ogg",
+        "wav" => "audio/wav",
+        "flac" => "audio/flac",
+        "weba" => "audio/webm",
+        "mid" | "midi" => "audio/midi",
+
+        // Video
+        "mp4" | "m4v" => "video/mp4",
+        "webm" => "video/webm",
+        "ogv" => "video/ogg",
+        "mov" => "video/quicktime",
+        "mkv" => "video/x-matroska",
+        "avi" => "video/x-msvideo",
+        "m3u8" => "application/vnd.apple.mpegurl",
+        "mpd" => "application/dash+xml",
+        "ts" => "video/mp2t",
+        "vtt" => "text/vtt; charset=utf-8",
+        "srt" => "application/x-subrip",
+
+        // 3D
+        "gltf" => "model/gltf+json",
+        "glb" => "model/gltf-binary",
+        "obj" => "model/obj",
+        "stl" => "model/stl",
+        "usdz" => "model/vnd.usdz+zip",
+
+        // Archives and binaries
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        "br" => "application/x-brotli",
+        "tar" => "application/x-tar",
+        "7z" => "application/x-7z-compressed",
+
+        _ => DEFAULT,
+    }
+}
+
+pub fn from_path(path: &Path) -> &'static str {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map_or(DEFAULT, from_extension)
+}
+
+/// Split "type/subtype; charset=x" into the essence and the charset, if any.
+pub fn split_charset(mime: &str) -> (&str, Option<&str>) {
+    let mut parts = mime.split(';');
+    let essence = parts.next().unwrap_or("").trim();
+
+    let charset = parts
+        .filter_map(|p| p.split_once('='))
+        .find(|(k, _)| k.trim().eq_ignore_ascii_case("charset"))
+        .map(|(_, v)| v.trim().trim_matches('"'));
+
+    (essence, charset)
+}
+
+/// Whether a type is worth compressing; images and media already are.
+pub fn is_compressible(mime: &str) -> bool {
+    let (essence, _) = split_charset(mime);
+
+    essence.starts_with("text/")
+        || essence.ends_with("+xml")
+        || essence.ends_with("+json")
+        || matches!(
+            essence,
+            "application/json"
+                | "application/xml"
+                | "application/wasm"
+                | "application/yaml"
+                | "application/toml"
+                | "model/obj"
+                | "model/stl"
+        )
+}
@@ -0,0 +1,143 @@
+//! The packed asset archive format (`.kpak`).
+//!
+//! Written by `kurogane bundle --pack` and served by the app:// handler
+//! straight from a memory map. Also compiled into kurogane-cli, so this
+//! file must only depend on std.
+//!
+//! Layout, integers little-endian:
+//!
+//! ```text
+//! magic      8 bytes   "KUROPAK\0"
+//! version    u32
+//! flags      u32       bit 0: every entry carries a SHA-256 digest
+//! index_len  u64
+//! index      entries sorted by path:
+//!              path_len u32, path (UTF-8, '/' separated),
+//!              offset u64, size u64, [sha256 32 bytes]
+//! data       file contents; offsets are relative to the start of data
+//! ```
+
+// Each crate uses a different subset
+#![allow(dead_code)]
+
+pub const MAGIC: &[u8; 8] = b"KUROPAK\0";
+pub const VERSION: u32 = 1;
+pub const FLAG_SHA256: u32 = 1;
+
+const HEADER_LEN: usize = 24;
+
+pub struct Entry {
+    pub path: String,
+    pub offset: u64,
+    pub size: u64,
+    pub sha256: Option<[u8; 32]>,
+}
+
+/// Header and index for `entries`, which must be sorted by path and either
+/// all carry a digest or none. File data follows directly.
+pub fn encode(entries: &[Entry]) -> Vec<u8> {
+    let hashed = entries.first().is_some_and(|e| e.sha256.is_some());
+
+    let mut index = Vec::new();
+    for entry in entries {
+        index.extend_from_slice(&(entry.path.len() as u32).to_le_bytes());
+        index.extend_from_slice(entry.path.as_bytes());
+        index.extend_from_slice(&entry.offset.to_le_bytes());
+        index.extend_from_slice(&entry.size.to_le_bytes());
+
+        if let Some(digest) = &entry.sha256 {
+            index.extend_from_slice(digest);
+        }
+    }
+
+    let mut out = Vec::with_capacity(HEADER_LEN + index.len());
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&VERSION.to_le_bytes());
+    out.extend_from_slice(&(if hashed { FLAG_SHA256 } else { 0 }).to_le_bytes());
+    out.extend_from_slice(&(index.len() as u64).to_le_bytes());
+    out.extend_from_slice(&index);
+    out
+}
+
+/// Parse the header and index of an archive of `bytes`.
+/// Returns the entries and the offset where data starts.
+pub fn decode(bytes: &[u8]) -> Result<(Vec<Entry>, usize), String> {
+    let mut r = Reader { bytes, pos: 0 };
+
+    if r.take(8)? != MAGIC {
+        return Err("not a kurogane archive".into());
+    }
+
+    let version = r.u32()?;
+    if version != VERSION {
+        return Err(format!("unsupported archive version {} (expected {})", version, VERSION));
+    }
+
+    let hashed = r.u32()? & FLAG_SHA256 != 0;
+
+    let index_len = usize::try_from(r.u64()?).map_err(|_| "index too large")?;
+    let data_start = HEADER_LEN
+        .checked_add(index_len)
+        .filter(|end| *end <= bytes.len())
+        .ok_or("index runs past the end of the archive")?;
+
+    let data_len = (bytes.len() - data_start) as u64;
+    let mut index = Reader { bytes: &bytes[..data_start], pos: HEADER_LEN };
+    let mut entries: Vec<Entry> = Vec::new();
+
+    while index.pos < data_start {
+        let path_len = index.u32()? as usize;
+        let path = std::str::from_utf8(index.take(path_len)?)
+            .map_err(|_| "entry path is not UTF-8")?
+            .to_string();
+
+        let offset = index.u64()?;
+        let size = index.u64()?;
+
+        let sha256 = if hashed {
+            Some(index.take(32)?.try_into().unwrap())
+        } else {
+            None
+        };
+
+        if offset.checked_add(size).is_none_or(|end| end > data_len) {
+            return Err(format!("entry {} runs past the end of the archive", path));
+        }
+
+        // Lookups binary search by path
+        if entries.last().is_some_and(|last| last.path >= path) {
+            return Err(format!("entry {} is out of order or duplicated", path));
+        }
+
+        entries.push(Entry { path, offset, size, sha256 });
+    }
+
+    Ok((entries, data_start))
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let end = self
+            .pos
+            .checked_add(n)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or("truncated archive index")?;
+
+        let out = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(out)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
@@ -144,6 +144,12 @@ impl Runtime {
         ASSET_ROOT.get().expect("asset root not set").clone()
     }
 
+    /// Whether this is the browser process. App::run executes in every CEF
+    /// process, and subprocesses are started with `--type=<role>`.
+    pub(crate) fn is_browser_process() -> bool {
+        !std::env::args().any(|arg| arg.starts_with("--type="))
+    }
+
     fn validate_asset_root() -> Result<(), RuntimeError> {
         let root = ASSET_ROOT.get().ok_or(RuntimeError::AssetRootNotSet)?;
 
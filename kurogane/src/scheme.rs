@@ -1,17 +1,46 @@
 //! app:// scheme support for local assets.
+//! One factory is registered per mount; see scheme/mount.rs.
+//! Files come from the mount's embedded tree or from its directory on disk.
+//! Disk files are opened and streamed in chunks on worker threads, never on CEF's IO thread.
+//! Range requests are answered with 206.
+//! Paths registered with App::route are answered by Rust handlers instead of files.
+//! A mount with a dev proxy forwards everything else to the dev server.
 
 use cef::*;
-use std::sync::{Arc, Mutex};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::ops::{Deref, Range};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::sync::mpsc::{self, Sender};
 use std::path::{Path, PathBuf};
-use std::sync::atomic::{AtomicI32, Ordering};
 use crate::debug;
+use crate::mime;
+use crate::{Archive, EmbeddedAssets};
+use crate::archive::ArchiveData;
+use memmap2::Mmap;
+
+pub(crate) mod config;
+pub(crate) mod glob;
+pub(crate) mod cache;
+mod compress;
+pub(crate) mod route;
+pub(crate) mod mount;
+mod url;
+mod proxy;
+
+use config::config;
+use mount::{Mount, MountSource};
+use route::{ResponseBody, RouteHandler, SchemeRequest, SchemeResponse};
 
 //
 // SchemeHandlerFactory
 //
 
 wrap_scheme_handler_factory! {
-    pub struct AppSchemeHandlerFactory;
+    pub struct AppSchemeHandlerFactory {
+        mount: &'static Mount,
+    }
 
     impl SchemeHandlerFactory {
         fn create(
@@ -23,76 +52,236 @@ wrap_scheme_handler_factory! {
         ) -> Option<ResourceHandler> {
 
             Some(AppResourceHandler::new(
-                Arc::new(Mutex::new(Vec::new())),
-                Arc::new(Mutex::new(0usize)),
-                Arc::new(Mutex::new(String::from("text/html"))),
-                Arc::new(AtomicI32::new(200)),
+                Some(self.mount),
+                Arc::new(Mutex::new(Reply::text(200, ""))),
             ))
         }
     }
 }
 
+/// Handler that sends `response` for any request, e.g. a mocked backend.
+pub(crate) fn canned(response: SchemeResponse) -> ResourceHandler {
+    AppResourceHandler::new(None, Arc::new(Mutex::new(Reply::from(response))))
+}
+
+//
+// Response state
+//
+
+/// What open() decided to send; consumed by response_headers() and read().
+struct Reply {
+    status: i32,
+    mime: String,
+    headers: Vec<(String, String)>,
+    body: Body,
+}
+
+enum Body {
+    /// Embedded or generated bytes; `pos..end` is still to be sent.
+    Memory { data: Bytes, pos: usize, end: usize },
+    /// File positioned at the first byte to send.
+    File { file: File, remaining: u64 },
+    /// Streamed route response of unknown length.
+    Stream(Box<dyn Read + Send>),
+    /// Dev server response, read on its own thread; see StreamThread.
+    Proxied { reader: Box<dyn Read + Send>, thread: StreamThread },
+}
+
+/// Embedded bytes, bytes shared with a cache, or a slice of a mapped archive.
+enum Bytes {
+    Static(&'static [u8]),
+    Shared(Arc<[u8]>),
+    Mapped(Arc<Mmap>, Range<usize>),
+}
+
+impl Deref for Bytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Bytes::Static(data) => data,
+            Bytes::Shared(data) => data,
+            Bytes::Mapped(map, range) => &map[range.clone()],
+        }
+    }
+}
+
+impl Reply {
+    fn text(status: i32, body: &'static str) -> Self {
+        Self {
+            status,
+            mime: "text/plain; charset=utf-8".into(),
+            headers: Vec::new(),
+            body: Body::memory(Bytes::Static(body.as_bytes())),
+        }
+    }
+}
+
+impl From<SchemeResponse> for Reply {
+    fn from(response: SchemeResponse) -> Self {
+        let body = match response.body {
+            ResponseBody::Buffered(data) => Body::memory(Bytes::Shared(data.into())),
+            ResponseBody::Stream(reader) => Body::Stream(reader),
+        };
+
+        Self {
+            status: response.status as i32,
+            mime: response.mime,
+            headers: response.headers,
+            body,
+        }
+    }
+}
+
+impl Body {
+    fn memory(data: Bytes) -> Self {
+        let end = data.len();
+        Body::Memory { data, pos: 0, end }
+    }
+
+    /// Bytes left to send, if known up front.
+    fn len(&self) -> Option<u64> {
+        match self {
+            Body::Memory { pos, end, .. } => Some((end - pos) as u64),
+            Body::File { remaining, .. } => Some(*remaining),
+            Body::Stream(_) | Body::Proxied { .. } => None,
+        }
+    }
+
+    /// Restrict the body to the inclusive byte range `start..=last`.
+    fn slice(self, start: u64, last: u64) -> std::io::Result<Self> {
+        Ok(match self {
+            Body::Memory { data, .. } => Body::Memory {
+                data,
+                pos: start as usize,
+                end: last as usize + 1,
+            },
+            Body::File { mut file, .. } => {
+                file.seek(SeekFrom::Start(start))?;
+                Body::File { file, remaining: last - start + 1 }
+            }
+            Body::Stream(_) | Body::Proxied { .. } => return Err(std::io::ErrorKind::Unsupported.into()),
+        })
+    }
+
+    /// Copy up to `out.len()` bytes; 0 means the body is complete.
+    fn read_into(&mut self, out: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Body::Memory { data, pos, end } => {
+                let n = (*end - *pos).min(out.len());
+                out[..n].copy_from_slice(&data[*pos..*pos + n]);
+                *pos += n;
+                Ok(n)
+            }
+            Body::File { file, remaining } => {
+                let want = (*remaining).min(out.len() as u64) as usize;
+                if want == 0 {
+                    return Ok(0);
+                }
+
+                let n = file.read(&mut out[..want])?;
+                *remaining -= n as u64;
+                Ok(n)
+            }
+            Body::Stream(reader) | Body::Proxied { reader, .. } => reader.read(out),
+        }
+    }
+
+    /// Dedicated thread for this body's reads, if it has one.
+    fn thread(&self) -> Option<StreamThread> {
+        match self {
+            Body::Proxied { thread, .. } => Some(thread.clone()),
+            _ => None,
+        }
+    }
+}
+
 //
 // ResourceHandler
 //
 
 wrap_resource_handler! {
     pub struct AppResourceHandler {
-        data: Arc<Mutex<Vec<u8>>>,
-        offset: Arc<Mutex<usize>>,
-        mime: Arc<Mutex<String>>,
-        status: Arc<AtomicI32>,
+        /// None when the reply was decided up front; see canned().
+        mount: Option<&'static Mount>,
+        reply: Arc<Mutex<Reply>>,
     }
 
     impl ResourceHandler {
 
-        // Synchronous open
         fn open(
             &self,
             request: Option<&mut Request>,
             handle_request: Option<&mut i32>,
-            _callback: Option<&mut Callback>,
+            callback: Option<&mut Callback>,
         ) -> i32 {
             let request = request.unwrap();
-            let url: CefString = (&request.url()).into();
-            let url = url.to_string();
 
-            // Strip scheme and handle trailing slashes
-            let path = url
-                .strip_prefix("app://app/")
-                .unwrap_or("index.html")
-                .trim_start_matches('/')
-                .trim_end_matches('/');
-
-            // Handle empty path (app:// or app:///)
-            let path = if path.is_empty() { "index.html" } else { path };
+            let Some(mount) = self.mount else {
+                if let Some(hr) = handle_request {
+                    *hr = 1;
+                }
+                return 1;
+            };
 
-            debug!("Resolved path: {}", path);
+            let info = match RequestInfo::new(request, mount) {
+                Ok(info) => info,
+                Err(e) => {
+                    eprintln!("[app://] 400 {}", e);
+                    let url: CefString = (&request.url()).into();
+                    *self.reply.lock().unwrap() = error_reply(400, "400 Bad Request", &url.to_string());
+                    if let Some(hr) = handle_request {
+                        *hr = 1;
+                    }
+                    return 1;
+                }
+            };
 
-            // Resolve relative to CWD (set by resolver)
-            let root = crate::runtime::Runtime::asset_root();
+            debug!("Resolved path: {}{}", mount.origin(), info.path);
 
-            let result = safe_join(&root, path)
-                .and_then(|p| std::fs::read(&p).ok().map(|b| (p, b)));
+            // Routes take priority over files
+            let handler = mount.handler_for(&info.path);
 
-            match result {
-                Some((full_path, bytes)) => {
-                    *self.data.lock().unwrap() = bytes;
-                    *self.offset.lock().unwrap() = 0;
-                    *self.mime.lock().unwrap() = mime_from_path(&full_path).to_string();
-                    self.status.store(200, Ordering::Release);
+            let job: Box<dyn FnOnce() -> Reply + Send> = match handler {
+                Some(handler) => {
+                    let request = scheme_request(request, &info);
+                    Box::new(move || routed(mount, handler, request))
                 }
-                None => {
-                    eprintln!("[app://] 404 {}", path);
-                    self.status.store(404, Ordering::Release);
-                    *self.data.lock().unwrap() = b"404 Not Found".to_vec();
-                    *self.offset.lock().unwrap() = 0;
-                    *self.mime.lock().unwrap() = "text/plain".into();
+                None => match &mount.source {
+                    Some(MountSource::Proxy(target)) => {
+                        let request = scheme_request(request, &info);
+                        Box::new(move || proxied(target, request, &info))
+                    }
+                    _ => Box::new(move || resolve(&info)),
+                },
+            };
+
+            // Embedded lookups never block; answer right away
+            let blocking = handler.is_some()
+                || !matches!(mount.source, Some(MountSource::Embedded(_)));
+
+            let callback = match callback {
+                Some(cb) if blocking => cb.clone(),
+                _ => {
+                    *self.reply.lock().unwrap() = guarded(job);
+                    if let Some(hr) = handle_request {
+                        *hr = 1;
+                    }
+                    return 1;
                 }
-            }
+            };
+
+            // Handlers and disk lookups run on a worker; CEF waits for the callback
+            let reply = self.reply.clone();
+            let callback = Detached(callback);
+
+            run_on_worker(move || {
+                *reply.lock().unwrap() = guarded(job);
+                callback.into_inner().cont();
+            });
 
             if let Some(hr) = handle_request {
-                *hr = 1;
+                *hr = 0;
             }
 
             1
@@ -103,23 +292,68 @@ wrap_resource_handler! {
             data_out: *mut u8,
             bytes_to_read: i32,
             bytes_read: Option<&mut i32>,
-            _callback: Option<&mut ResourceReadCallback>,
+            callback: Option<&mut ResourceReadCallback>,
         ) -> i32 {
             let br = bytes_read.unwrap();
+            let len = bytes_to_read.max(0) as usize;
 
-            let mut offset = self.offset.lock().unwrap();
-            let data = self.data.lock().unwrap();
+            let (blocking, thread) = {
+                let reply = self.reply.lock().unwrap();
+                let blocking = matches!(reply.body, Body::File { .. } | Body::Stream(_) | Body::Proxied { .. });
+                (blocking, reply.body.thread())
+            };
 
-            let remaining = &data[*offset..];
-            let read = remaining.len().min(bytes_to_read as usize);
+            // Disk and stream reads complete asynchronously; data_out stays valid until the callback runs
+            if let (true, Some(callback)) = (blocking, callback) {
+                let reply = self.reply.clone();
+                let out = Detached((data_out, len));
+                let callback = Detached(callback.clone());
 
-            if read > 0 {
-                unsafe {
-                    std::ptr::copy_nonoverlapping(remaining.as_ptr(), data_out, read);
+                let job = move || {
+                    let (ptr, len) = out.into_inner();
+                    // SAFETY: CEF keeps data_out (len bytes) alive until cont() is called
+                    let out = unsafe { std::slice::from_raw_parts_mut(ptr, len) };
+
+                    // The guard lives outside catch_unwind, so a panicking
+                    // stream fails this response without poisoning the lock
+                    let mut reply = reply.lock().unwrap();
+                    let read = match panic::catch_unwind(AssertUnwindSafe(|| reply.body.read_into(out))) {
+                        Ok(Ok(n)) => n as i32,
+                        Ok(Err(e)) => {
+                            eprintln!("[app://] read failed: {}", e);
+                            ERR_FAILED
+                        }
+                        Err(_) => {
+                            eprintln!("[app://] response body panicked while reading");
+                            ERR_FAILED
+                        }
+                    };
+                    drop(reply);
+
+                    // 0 completes the response, negative values abort it
+                    callback.into_inner().cont(read);
+                };
+
+                match thread {
+                    Some(thread) => thread.run(job),
+                    None => run_on_worker(job),
                 }
-                *offset += read;
+
+                *br = 0;
+                return 1;
             }
 
+            // SAFETY: CEF guarantees data_out holds bytes_to_read bytes for this call
+            let out = unsafe { std::slice::from_raw_parts_mut(data_out, len) };
+
+            let read = match self.reply.lock().unwrap().body.read_into(out) {
+                Ok(n) => n,
+                Err(e) => {
+                    eprintln!("[app://] read failed: {}", e);
+                    0
+                }
+            };
+
             *br = read as i32;
 
             if read == 0 {
@@ -133,44 +367,733 @@ wrap_resource_handler! {
             &self,
             response: Option<&mut Response>,
             response_length: Option<&mut i64>,
-            _redirect_url: Option<&mut CefString>,
+            redirect_url: Option<&mut CefString>,
         ) {
             let response = response.unwrap();
+            let reply = self.reply.lock().unwrap();
 
-            let status = self.status.load(Ordering::Acquire);
-            let data_len = self.data.lock().unwrap().len() as i64;
-            let mime = self.mime.lock().unwrap().clone();
+            // CEF takes the charset separately from the MIME type
+            let (essence, charset) = mime::split_charset(&reply.mime);
 
-            response.set_status(status);
-            response.set_mime_type(Some(&CefString::from(mime.as_str())));
+            response.set_status(reply.status);
+            response.set_mime_type(Some(&CefString::from(essence)));
 
+            if let Some(charset) = charset {
+                response.set_charset(Some(&CefString::from(charset)));
+            }
+
+            // CEF follows redirects through redirect_url, not the Location header
+            let location = reply.headers.iter().find(|(n, _)| n.eq_ignore_ascii_case("Location"));
+
+            if let (300..=399, Some((_, location)), Some(redirect)) = (reply.status, location, redirect_url) {
+                *redirect = CefString::from(location.as_str());
+            }
+
+            for (name, value) in &reply.headers {
+                response.set_header_by_name(
+                    Some(&CefString::from(name.as_str())),
+                    Some(&CefString::from(value.as_str())),
+                    1,
+                );
+            }
+
+            // -1: unknown, read until read() reports the end
             if let Some(len) = response_length {
-                *len = data_len;
+                *len = reply.body.len().map_or(-1, |len| len as i64);
             }
         }
     }
 }
 
+//
+// Worker threads
+//
+
+// net::ERR_FAILED, reported to CEF when a disk read fails mid-response
+const ERR_FAILED: i32 = -2;
+
+const IO_WORKERS: usize = 4;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Moves a CEF callback or CEF-owned buffer to a worker thread.
+///
+/// SAFETY: CEF callbacks are thread-safe ref-counted objects that may be
+/// executed on any thread, and CEF keeps read buffers alive until the
+/// matching callback runs. Nothing else is ever wrapped in this.
+struct Detached<T>(T);
+unsafe impl<T> Send for Detached<T> {}
+
+impl<T> Detached<T> {
+    // A method call moves the whole wrapper into a closure; `.0` would
+    // capture only the non-Send field.
+    fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+/// One thread per dev server response. Such streams can stay open for
+/// minutes (SSE, slow servers), and must not hold a shared IO worker that
+/// every mount depends on. The thread exits once the body is dropped.
+#[derive(Clone)]
+struct StreamThread(Sender<Job>);
+
+impl StreamThread {
+    fn spawn() -> std::io::Result<Self> {
+        let (tx, rx) = mpsc::channel::<Job>();
+
+        std::thread::Builder::new()
+            .name("kurogane-proxy".into())
+            .spawn(move || {
+                for job in rx {
+                    if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+                        eprintln!("[app://] proxied read panicked");
+                    }
+                }
+            })?;
+
+        Ok(Self(tx))
+    }
+
+    fn run(&self, job: impl FnOnce() + Send + 'static) {
+        if let Err(mpsc::SendError(job)) = self.0.send(Box::new(job)) {
+            run_on_worker(job);
+        }
+    }
+}
+
+/// Build a reply, answering 500 if building it panics. CEF only learns
+/// the reply is ready from the callback, so a panic must not skip it.
+fn guarded(job: impl FnOnce() -> Reply) -> Reply {
+    panic::catch_unwind(AssertUnwindSafe(job)).unwrap_or_else(|_| {
+        eprintln!("[app://] request handling panicked");
+        Reply::text(500, "500 Internal Server Error")
+    })
+}
+
+/// Run `job` on the shared pool of IO workers.
+fn run_on_worker(job: impl FnOnce() + Send + 'static) {
+    static POOL: OnceLock<Mutex<Sender<Job>>> = OnceLock::new();
+
+    let pool = POOL.get_or_init(|| {
+        let (tx, rx) = mpsc::channel::<Job>();
+        let rx = Arc::new(Mutex::new(rx));
+
+        for i in 0..IO_WORKERS {
+            let rx = rx.clone();
+            std::thread::Builder::new()
+                .name(format!("kurogane-io-{}", i))
+                .spawn(move || loop {
+                    // Hold the lock only while waiting, not while running the job
+                    let job = rx.lock().unwrap().recv();
+                    match job {
+                        // Jobs catch their own panics; this keeps the worker
+                        // alive should one slip through
+                        Ok(job) => {
+                            if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+                                eprintln!("[app://] IO job panicked");
+                            }
+                        }
+                        Err(_) => break,
+                    }
+                })
+                .expect("failed to spawn app:// IO worker");
+        }
+
+        Mutex::new(tx)
+    });
+
+    // Only fails if every worker is gone; run here rather than never
+    if let Err(mpsc::SendError(job)) = pool.lock().unwrap().send(Box::new(job)) {
+        eprintln!("[app://] IO workers unavailable, running job inline");
+        job();
+    }
+}
+
 //
 // Helpers
 //
 
-fn mime_from_path(path: &Path) -> &'static str {
-    match path.extension().and_then(|e| e.to_str()) {
-        Some("html") => "text/html",
-        Some("js") => "application/javascript",
-        Some("css") => "text/css",
-        Some("json") => "application/json",
-        Some("wasm") => "application/wasm",
-        Some("svg") => "image/svg+xml",
-        Some("png") => "image/png",
-        Some("jpg" | "jpeg") => "image/jpeg",
-        Some("ico") => "image/x-icon",
-        _ => "application/octet-stream",
+const INDEX: &str = "index.html";
+
+/// Request fields the reply depends on, copied out so lookups can run on a worker.
+struct RequestInfo {
+    mount: &'static Mount,
+    /// The URL without its fragment, as requested.
+    url: String,
+    /// Decoded path relative to the mount, "" for the root.
+    path: String,
+    query: String,
+    /// Path ended in a slash.
+    dir: bool,
+    range: Option<String>,
+    accept_encoding: String,
+    if_none_match: Option<String>,
+    if_modified_since: Option<String>,
+}
+
+impl RequestInfo {
+    fn new(request: &Request, mount: &'static Mount) -> Result<Self, String> {
+        let url: CefString = (&request.url()).into();
+        let url = url.to_string();
+        let url = url.split('#').next().unwrap_or_default().to_string();
+
+        let parsed = url::parse(&url, &mount.origin())?;
+
+        Ok(Self {
+            mount,
+            url,
+            path: parsed.path,
+            query: parsed.query,
+            dir: parsed.dir,
+            range: header(request, "Range"),
+            accept_encoding: header(request, "Accept-Encoding").unwrap_or_default(),
+            if_none_match: header(request, "If-None-Match"),
+            if_modified_since: header(request, "If-Modified-Since"),
+        })
+    }
+
+    /// Whether Accept-Encoding lists `encoding`.
+    fn accepts(&self, encoding: &str) -> bool {
+        self.accept_encoding
+            .split(',')
+            .any(|e| e.split(';').next().unwrap_or("").trim().eq_ignore_ascii_case(encoding))
     }
 }
 
-fn safe_join(root: &Path, request: &str) -> Option<PathBuf> {
+/// Build the complete reply for a request. May block on disk.
+fn resolve(info: &RequestInfo) -> Reply {
+    let config = config();
+    let mount = info.mount;
+
+    // Hidden files such as .env or .git are not served unless allowed
+    let found = if !mount.dotfiles && url::has_dotfile(&info.path) {
+        None
+    } else {
+        lookup(info)
+    };
+
+    let mut reply = match found {
+        Some(Err(reply)) => reply,
+        Some(Ok((body, mime, mut headers))) => {
+            if config.no_cache {
+                // Nothing to revalidate against; always send the current bytes
+                headers.retain(|(n, _)| n != "ETag" && n != "Last-Modified");
+                headers.push(("Cache-Control".into(), "no-store".into()));
+            } else {
+                headers.push(("Cache-Control".into(), mount.cache_control_for(&info.path).into()));
+            }
+
+            let not_modified = cache::not_modified(
+                info.if_none_match.as_deref(),
+                info.if_modified_since.as_deref(),
+                &headers,
+            );
+
+            if not_modified {
+                Reply { status: 304, mime, headers, body: Body::memory(Bytes::Static(&[])) }
+            } else {
+                respond(body, mime, headers, info.range.as_deref())
+            }
+        }
+        None => {
+            eprintln!("[app://] 404 {}", info.path);
+            not_found(info)
+        }
+    };
+
+    // Error replies need them too, e.g. COEP on a missing worker script
+    mount.apply_header_rules(&info.path, &mut reply.headers);
+
+    reply
+}
+
+/// Find the file for a request: the path itself, a directory index, or the
+/// SPA fallback.
+fn lookup(info: &RequestInfo) -> Option<Loaded> {
+    let index = match info.path.as_str() {
+        "" => INDEX.to_string(),
+        dir => format!("{}/{}", dir, INDEX),
+    };
+
+    let found = if info.dir {
+        load(info, &index)
+    } else {
+        load(info, &info.path).or_else(|| {
+            // A directory named without its trailing slash; redirect so
+            // relative URLs in its index resolve inside it
+            load(info, &index)?;
+
+            let (path, query) = info.url.split_once('?').unwrap_or((&info.url, ""));
+            let location = match query {
+                "" => format!("{}/", path),
+                query => format!("{}/?{}", path, query),
+            };
+
+            Some(Err(Reply {
+                status: 301,
+                mime: "text/plain; charset=utf-8".into(),
+                headers: vec![("Location".into(), location)],
+                body: Body::memory(Bytes::Static(&[])),
+            }))
+        })
+    };
+
+    // Client-side routes have no file; let the frontend router handle them
+    found.or_else(|| {
+        if !info.mount.falls_back(&info.path) {
+            return None;
+        }
+        debug!("SPA fallback: {}", info.path);
+        load(info, INDEX)
+    })
+}
+
+/// Reply for a missing path: the App::error_page renderer's HTML, else the
+/// not-found page from the mount, else plain text.
+fn not_found(info: &RequestInfo) -> Reply {
+    let page = || {
+        let path = info.mount.not_found_page.as_deref()?;
+
+        match load(info, path)? {
+            Ok((body, mime, mut headers)) => {
+                // The page stands in for a missing file; nothing to revalidate
+                headers.retain(|(n, _)| n != "ETag" && n != "Last-Modified");
+                Some(Reply { status: 404, mime, headers, body })
+            }
+            Err(_) => None,
+        }
+    };
+
+    rendered(404, &info.url)
+        .or_else(page)
+        .unwrap_or_else(|| Reply::text(404, "404 Not Found"))
+}
+
+/// Error reply with the App::error_page renderer's HTML, else `text`.
+fn error_reply(status: i32, text: &'static str, url: &str) -> Reply {
+    rendered(status, url).unwrap_or_else(|| Reply::text(status, text))
+}
+
+fn rendered(status: i32, url: &str) -> Option<Reply> {
+    let renderer = config().error_renderer.as_ref()?;
+
+    let html = panic::catch_unwind(AssertUnwindSafe(|| renderer(status as u16, url)))
+        .unwrap_or_else(|_| {
+            eprintln!("[app://] error page renderer panicked for {} {}", status, url);
+            None
+        })?;
+
+    Some(Reply {
+        status,
+        mime: "text/html; charset=utf-8".into(),
+        headers: Vec::new(),
+        body: Body::memory(Bytes::Shared(html.into_bytes().into())),
+    })
+}
+
+/// Run a route handler of `mount` and turn its response into a reply.
+fn routed(mount: &Mount, handler: &RouteHandler, request: SchemeRequest) -> Reply {
+    let path = request.path.clone();
+
+    // A panicking handler fails its request, not the IO worker
+    let response = panic::catch_unwind(AssertUnwindSafe(|| handler(request)))
+        .unwrap_or_else(|_| {
+            eprintln!("[app://] route handler for {} panicked", path);
+            SchemeResponse::text("500 Internal Server Error").status(500)
+        });
+
+    let mut reply = Reply::from(response);
+
+    mount.apply_header_rules(path.trim_start_matches('/'), &mut reply.headers);
+
+    reply
+}
+
+/// Forward a request to the mount's dev server and stream back its response.
+fn proxied(target: &str, request: SchemeRequest, info: &RequestInfo) -> Reply {
+    let origin = info.mount.origin();
+
+    // Path and query exactly as requested, still percent-encoded
+    let raw = info.url.get(origin.len()..).unwrap_or_default();
+
+    let forwarded = proxy::Upstream::parse(target).and_then(|upstream| {
+        let response = upstream
+            .forward(&request.method, raw, &request.headers, &request.body)
+            .map_err(|e| e.to_string())?;
+        Ok((upstream, response))
+    });
+
+    let mut reply = match forwarded {
+        Ok((upstream, response)) => {
+            let mut headers = response.headers;
+
+            // Keep redirects on the app origin
+            for (name, value) in &mut headers {
+                if name.eq_ignore_ascii_case("Location") {
+                    *value = upstream.rewrite_location(value, &origin);
+                }
+            }
+
+            let body = match StreamThread::spawn() {
+                Ok(thread) => Body::Proxied { reader: response.body, thread },
+                Err(e) => {
+                    eprintln!("[app://] no thread for {}{}: {}; reading on the IO pool", origin, raw, e);
+                    Body::Stream(response.body)
+                }
+            };
+
+            Reply { status: response.status, mime: response.mime, headers, body }
+        }
+        Err(e) => {
+            eprintln!("[app://] 502 {}{}: dev server {}: {}", origin, raw, target, e);
+            error_reply(502, "502 Dev server unreachable", &info.url)
+        }
+    };
+
+    info.mount.apply_header_rules(&info.path, &mut reply.headers);
+
+    reply
+}
+
+/// Copy everything a route handler may look at out of the CEF request.
+fn scheme_request(request: &Request, info: &RequestInfo) -> SchemeRequest {
+    let url: CefString = (&request.url()).into();
+    let method: CefString = (&request.method()).into();
+
+    let mut map = CefStringMultimap::new();
+    request.header_map(Some(&mut map));
+
+    SchemeRequest {
+        method: method.to_string(),
+        url: url.to_string(),
+        path: format!("/{}", info.path),
+        query: info.query.clone(),
+        headers: map.into_iter().collect(),
+        body: request_body(request),
+    }
+}
+
+/// Concatenated bytes of the request's post data; file elements are skipped.
+pub(crate) fn request_body(request: &Request) -> Vec<u8> {
+    let Some(post) = request.post_data() else {
+        return Vec::new();
+    };
+
+    let mut elements = vec![None; post.element_count()];
+    post.elements(Some(&mut elements));
+
+    let mut body = Vec::new();
+
+    for element in elements.into_iter().flatten() {
+        let n = element.bytes_count();
+        let start = body.len();
+
+        body.resize(start + n, 0);
+        let copied = element.bytes(n, body[start..].as_mut_ptr().cast());
+        body.truncate(start + copied);
+    }
+
+    body
+}
+
+/// A file to send: body, MIME type and headers. Err is a finished error reply.
+type Loaded = Result<(Body, String, Vec<(String, String)>), Reply>;
+
+/// Find `path` in the mount's embedded tree, archive or directory.
+/// Files on disk are opened, not read; their bytes are streamed by read().
+fn load(info: &RequestInfo, path: &str) -> Option<Loaded> {
+    let config = config();
+
+    let root = match info.mount.source.as_ref()? {
+        MountSource::Dir(root) => root,
+        MountSource::Embedded(assets) => return load_embedded(info, assets, path),
+        MountSource::Archive(archive) => return load_archive(info, archive, path),
+        // Forwarded in open(), never looked up
+        MountSource::Proxy(_) => return None,
+    };
+
+    let full_path = safe_join(info.mount, root, path)?;
+    let file = File::open(&full_path).ok()?;
+    let meta = file.metadata().ok()?;
+
+    if !meta.is_file() {
+        return None;
+    }
+
+    let mime = config.mime_type(path, mime::from_path(&full_path));
+
+    if info.range.is_none() {
+        // Precompressed siblings written by the bundler, best first
+        for (encoding, suffix) in [("br", "br"), ("gzip", "gz")] {
+            if !info.accepts(encoding) {
+                continue;
+            }
+
+            let Some(sibling) = safe_join(info.mount, root, &format!("{}.{}", path, suffix)) else {
+                continue;
+            };
+
+            if let Ok(file) = File::open(&sibling)
+                && let Some(meta) = file.metadata().ok().filter(|m| m.is_file())
+            {
+                let mut headers = encoded(encoding);
+                headers.extend(cache::file_validators(&meta, None));
+
+                let body = Body::File { file, remaining: meta.len() };
+                return Some(Ok((body, mime, headers)));
+            }
+        }
+
+        if config.compress
+            && info.accepts("gzip")
+            && let Some(gz) = compress::gzip(&full_path, &meta, &mime)
+        {
+            let mut headers = encoded("gzip");
+            headers.extend(cache::file_validators(&meta, Some("gzip")));
+            return Some(Ok((Body::memory(Bytes::Shared(gz)), mime, headers)));
+        }
+    }
+
+    let headers = cache::file_validators(&meta, None);
+    Some(Ok((Body::File { file, remaining: meta.len() }, mime, headers)))
+}
+
+/// Find `path` in an embedded tree; never blocks.
+fn load_embedded(
+    info: &RequestInfo,
+    assets: &'static EmbeddedAssets,
+    path: &str,
+) -> Option<Loaded> {
+    let file = assets.get(path)?;
+    let mime = config().mime_type(path, file.mime());
+
+    // Ranges refer to the identity encoding, so only whole bodies are encoded
+    if info.range.is_none() {
+        // A .br sibling from the bundler, then gzip made at build time
+        if let Some(br) = assets.get(&format!("{}.br", path)).filter(|_| info.accepts("br")) {
+            let mut headers = encoded("br");
+            headers.push(cache::etag_header(br.etag(), Some("br")));
+            return Some(Ok((Body::memory(Bytes::Static(br.data())), mime, headers)));
+        }
+
+        if let Some(gz) = file.gzip().filter(|_| info.accepts("gzip")) {
+            let mut headers = encoded("gzip");
+            headers.push(cache::etag_header(file.etag(), Some("gzip")));
+            return Some(Ok((Body::memory(Bytes::Static(gz)), mime, headers)));
+        }
+    }
+
+    let headers = vec![cache::etag_header(file.etag(), None)];
+    Some(Ok((Body::memory(Bytes::Static(file.data())), mime, headers)))
+}
+
+/// Find `path` in a packed archive. Files failing their integrity check
+/// are refused with a 500 rather than served.
+fn load_archive(
+    info: &RequestInfo,
+    archive: &Archive,
+    path: &str,
+) -> Option<Loaded> {
+    let mime = config().mime_type(path, mime::from_path(Path::new(path)));
+
+    // Precompressed siblings packed from the bundler output, best first
+    let variants = [("br", "br"), ("gzip", "gz")]
+        .into_iter()
+        .filter(|(encoding, _)| info.range.is_none() && info.accepts(encoding))
+        .map(|(encoding, suffix)| (Some(encoding), format!("{}.{}", path, suffix)))
+        .chain([(None, path.to_string())]);
+
+    for (encoding, name) in variants {
+        let file = match archive.get(&name) {
+            Some(Ok(file)) => file,
+            None if encoding.is_some() => continue,
+            None => return None,
+
+            // Fall back to the identity file, which is checked separately
+            Some(Err(e)) if encoding.is_some() => {
+                eprintln!("[app://] {}", e);
+                continue;
+            }
+            Some(Err(e)) => {
+                eprintln!("[app://] {}", e);
+                return Some(Err(error_reply(500, "500 Integrity check failed", &info.url)));
+            }
+        };
+
+        let mut headers = encoding.map(encoded).unwrap_or_default();
+        headers.push(("ETag".into(), file.etag));
+
+        let bytes = match file.data {
+            ArchiveData::Mapped(map, range) => Bytes::Mapped(map, range),
+            ArchiveData::Verified(data) => Bytes::Shared(data),
+        };
+        return Some(Ok((Body::memory(bytes), mime, headers)));
+    }
+
+    None
+}
+
+/// Headers for a body sent with `encoding`.
+fn encoded(encoding: &str) -> Vec<(String, String)> {
+    vec![
+        ("Content-Encoding".to_string(), encoding.to_string()),
+        ("Vary".to_string(), "Accept-Encoding".to_string()),
+    ]
+}
+
+/// Build a 200, 206 or 416 reply for `body` depending on the Range header.
+///
+/// Encoded variants are always sent whole: offsets would count encoded
+/// bytes, which a client asking for a range doesn't know about.
+fn respond(
+    body: Body,
+    mime: String,
+    mut headers: Vec<(String, String)>,
+    range: Option<&str>,
+) -> Reply {
+    // Files and memory bodies always have a length
+    let total = body.len().unwrap_or_default();
+
+    let encoded = headers.iter().any(|(n, _)| n.eq_ignore_ascii_case("Content-Encoding"));
+    let range = if encoded {
+        None
+    } else {
+        headers.push(("Accept-Ranges".into(), "bytes".into()));
+        range
+    };
+
+    let (status, body) = match range.map(|r| parse_range(r, total)) {
+        // Absent, malformed or multi-range: send the whole file
+        None | Some(None) => (200, body),
+
+        Some(Some(Some((start, last)))) => match body.slice(start, last) {
+            Ok(body) => {
+                headers.push((
+                    "Content-Range".into(),
+                    format!("bytes {}-{}/{}", start, last, total),
+                ));
+                (206, body)
+            }
+            Err(e) => {
+                eprintln!("[app://] seek failed: {}", e);
+                return Reply::text(500, "500 Internal Server Error");
+            }
+        },
+
+        Some(Some(None)) => {
+            headers.push(("Content-Range".into(), format!("bytes */{}", total)));
+            return Reply {
+                status: 416,
+                mime,
+                headers,
+                body: Body::memory(Bytes::Static(&[])),
+            };
+        }
+    };
+
+    Reply { status, mime, headers, body }
+}
+
+/// Parse a single `bytes=` range against a body of `total` bytes.
+///
+/// None: not a range we serve, answer with the full body.
+/// Some(None): unsatisfiable, answer 416.
+/// Some(Some((start, last))): inclusive byte range.
+fn parse_range(header: &str, total: u64) -> Option<Option<(u64, u64)>> {
+    let spec = header.trim().strip_prefix("bytes=")?;
+
+    // Multiple ranges would need multipart/byteranges
+    if spec.contains(',') {
+        return None;
+    }
+
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = (start.trim(), end.trim());
+
+    let range = if start.is_empty() {
+        // Suffix range: the last N bytes
+        let n: u64 = end.parse().ok()?;
+        (n > 0 && total > 0).then(|| (total.saturating_sub(n), total - 1))
+    } else {
+        let start: u64 = start.parse().ok()?;
+        let last = if end.is_empty() {
+            total.saturating_sub(1)
+        } else {
+            let end: u64 = end.parse().ok()?;
+            if end < start {
+                return None;
+            }
+            end.min(total.saturating_sub(1))
+        };
+
+        (start < total).then_some((start, last))
+    };
+
+    Some(range)
+}
+
+/// Value of a request header, if present and non-empty.
+fn header(request: &Request, name: &str) -> Option<String> {
+    let value: CefString = (&request.header_by_name(Some(&CefString::from(name)))).into();
+    let value = value.to_string();
+    (!value.is_empty()).then_some(value)
+}
+
+/// Resolve `request` below `root`, following symlinks.
+///
+/// url::parse has rejected `..` and encoded separators, so only a symlink
+/// can lead outside the root; that is refused unless `mount` allows it.
+fn safe_join(mount: &Mount, root: &Path, request: &str) -> Option<PathBuf> {
     let canonical = root.join(request).canonicalize().ok()?;
-    canonical.starts_with(root).then_some(canonical)
+    (canonical.starts_with(root) || mount.external_symlinks).then_some(canonical)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_byte_ranges() {
+        assert_eq!(parse_range("bytes=0-99", 1000), Some(Some((0, 99))));
+        assert_eq!(parse_range(" bytes=10-10 ", 1000), Some(Some((10, 10))));
+
+        // Open-ended, and an end past the body
+        assert_eq!(parse_range("bytes=900-", 1000), Some(Some((900, 999))));
+        assert_eq!(parse_range("bytes=900-5000", 1000), Some(Some((900, 999))));
+
+        // Suffix: the last N bytes, at most the whole body
+        assert_eq!(parse_range("bytes=-100", 1000), Some(Some((900, 999))));
+        assert_eq!(parse_range("bytes=-5000", 1000), Some(Some((0, 999))));
+    }
+
+    #[test]
+    fn flags_unsatisfiable_ranges() {
+        assert_eq!(parse_range("bytes=1000-", 1000), Some(None));
+        assert_eq!(parse_range("bytes=1000-1001", 1000), Some(None));
+        assert_eq!(parse_range("bytes=-0", 1000), Some(None));
+        assert_eq!(parse_range("bytes=0-", 0), Some(None));
+        assert_eq!(parse_range("bytes=-10", 0), Some(None));
+    }
+
+    #[test]
+    fn serves_encoded_variants_whole() {
+        let has = |reply: &Reply, name: &str| reply.headers.iter().any(|(n, _)| n == name);
+        let body = || Body::memory(Bytes::Static(b"0123456789"));
+
+        let reply = respond(body(), "text/plain".into(), Vec::new(), Some("bytes=2-3"));
+        assert_eq!(reply.status, 206);
+        assert!(has(&reply, "Accept-Ranges") && has(&reply, "Content-Range"));
+
+        let reply = respond(body(), "text/plain".into(), encoded("gzip"), Some("bytes=2-3"));
+        assert_eq!(reply.status, 200);
+        assert!(!has(&reply, "Accept-Ranges") && !has(&reply, "Content-Range"));
+    }
+
+    #[test]
+    fn ignores_ranges_it_does_not_serve() {
+        for header in ["", "items=0-1", "bytes=0-1,5-6", "bytes=5-1", "bytes=a-", "bytes=-", "bytes=1"] {
+            assert_eq!(parse_range(header, 1000), None, "{:?}", header);
+        }
+    }
 }
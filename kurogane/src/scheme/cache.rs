@@ -0,0 +1,217 @@
+//! HTTP validators and conditional requests for app://.
+//!
+//! Embedded files carry a content hash computed at build time. Files on
+//! disk are identified by size and modification time, and also get a
+//! Last-Modified date.
+
+use std::fs::Metadata;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub(crate) type Headers = Vec<(String, String)>;
+
+/// ETag and Last-Modified headers for a file on disk.
+pub(crate) fn file_validators(meta: &Metadata, encoding: Option<&str>) -> Headers {
+    let modified = meta.modified().ok();
+
+    let stamp = modified
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_nanos());
+
+    let etag = format!("\"{:x}-{:x}\"", meta.len(), stamp);
+    let mut headers = vec![etag_header(&etag, encoding)];
+
+    if let Some(modified) = modified {
+        headers.push(("Last-Modified".into(), http_date(modified)));
+    }
+
+    headers
+}
+
+/// ETag header for `etag`, marked with the content encoding if any, since
+/// each encoding is a different representation.
+pub(crate) fn etag_header(etag: &str, encoding: Option<&str>) -> (String, String) {
+    let value = match encoding {
+        Some(encoding) => format!("{}-{}\"", etag.trim_end_matches('"'), encoding),
+        None => etag.to_string(),
+    };
+
+    ("ETag".into(), value)
+}
+
+/// Whether the request's validators match the response `headers`.
+///
+/// If-None-Match takes precedence; If-Modified-Since is only consulted
+/// without it, as RFC 9110 requires.
+pub(crate) fn not_modified(
+    if_none_match: Option<&str>,
+    if_modified_since: Option<&str>,
+    headers: &[(String, String)],
+) -> bool {
+    let get = |name: &str| {
+        headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    };
+
+    if let Some(candidates) = if_none_match {
+        let Some(etag) = get("ETag") else { return false };
+
+        // Weak comparison: W/ prefixes are ignored
+        return candidates.split(',').map(str::trim).any(|c| {
+            c == "*" || c.trim_start_matches("W/") == etag.trim_start_matches("W/")
+        });
+    }
+
+    let (Some(since), Some(modified)) = (if_modified_since, get("Last-Modified")) else {
+        return false;
+    };
+
+    match (parse_http_date(since), parse_http_date(modified)) {
+        (Some(since), Some(modified)) => modified <= since,
+        _ => false,
+    }
+}
+
+const DAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun",
+    "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Format as an IMF-fixdate, e.g. "Sun, 06 Nov 1994 08:49:37 GMT".
+fn http_date(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+
+    let (year, month, day) = civil_from_days(days);
+
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        DAYS[(days % 7) as usize],
+        day,
+        MONTHS[month as usize - 1],
+        year,
+        rem / 3600,
+        rem / 60 % 60,
+        rem % 60,
+    )
+}
+
+/// Parse an IMF-fixdate; the obsolete formats are not produced by Chromium.
+fn parse_http_date(value: &str) -> Option<SystemTime> {
+    let mut parts = value.split_whitespace();
+
+    let _weekday = parts.next()?;
+    let day: u32 = parts.next()?.parse().ok()?;
+    let month = parts.next()?;
+    let month = MONTHS.iter().position(|m| *m == month)? as u32 + 1;
+    let year: i64 = parts.next()?.parse().ok()?;
+
+    let mut clock = parts.next()?.split(':').map(|p| p.parse::<u64>().ok());
+    let (h, m, s) = (clock.next()??, clock.next()??, clock.next()??);
+
+    // The header comes from the page; a date outside this range is ignored
+    // rather than allowed to overflow
+    if parts.next()? != "GMT"
+        || !(1970..=9999).contains(&year)
+        || !(1..=31).contains(&day)
+        || h > 23
+        || m > 59
+        || s > 60
+    {
+        return None;
+    }
+
+    let days = u64::try_from(days_from_civil(year, month, day)).ok()?;
+    let secs = days
+        .checked_mul(86_400)?
+        .checked_add(h * 3600 + m * 60 + s)?;
+
+    UNIX_EPOCH.checked_add(Duration::from_secs(secs))
+}
+
+// Howard Hinnant's civil date algorithms, days relative to 1970-01-01
+
+pub(crate) fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (i64::from(month) + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+    era * 146_097 + doe - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_http_dates() {
+        let time = UNIX_EPOCH + Duration::from_secs(784_111_777);
+        assert_eq!(http_date(time), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"), Some(time));
+        assert_eq!(parse_http_date("Thu, 01 Jan 1970 00:00:00 GMT"), Some(UNIX_EPOCH));
+        assert!(parse_http_date("Fri, 31 Dec 9999 23:59:59 GMT").is_some());
+    }
+
+    #[test]
+    fn ignores_out_of_range_years() {
+        for value in [
+            "Sun, 01 Jan 500000000000 00:00:00 GMT",
+            "Sun, 01 Jan 9223372036854775807 23:59:59 GMT",
+            "Sun, 01 Jan -9223372036854775808 00:00:00 GMT",
+            "Sat, 01 Jan 10000 00:00:00 GMT",
+            "Wed, 31 Dec 1969 23:59:59 GMT",
+        ] {
+            assert_eq!(parse_http_date(value), None, "{}", value);
+        }
+    }
+
+    #[test]
+    fn ignores_malformed_dates() {
+        for value in [
+            "",
+            "Sun, 06 Nov 1994",
+            "Sun, 06 Nov 1994 08:49:37",
+            "Sun, 06 Nov 1994 08:49:37 UTC",
+            "Sun, 06 Foo 1994 08:49:37 GMT",
+            "Sun, 32 Nov 1994 08:49:37 GMT",
+            "Sun, 00 Nov 1994 08:49:37 GMT",
+            "Sun, 06 Nov 1994 24:00:00 GMT",
+            "Sun, 06 Nov 1994 08:60:00 GMT",
+            "Sun, 06 Nov 1994 08:49 GMT",
+            "Sun, 06 Nov 1994 08:49:x GMT",
+            "Sun, 06 Nov 1994 99999999999999999999:00:00 GMT",
+            "Sunday, 06-Nov-94 08:49:37 GMT",
+        ] {
+            assert_eq!(parse_http_date(value), None, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn huge_if_modified_since_is_ignored() {
+        let headers = vec![("Last-Modified".to_string(), "Sun, 06 Nov 1994 08:49:37 GMT".to_string())];
+
+        assert!(!not_modified(None, Some("Sun, 01 Jan 500000000000 00:00:00 GMT"), &headers));
+        assert!(not_modified(None, Some("Mon, 07 Nov 1994 00:00:00 GMT"), &headers));
+        assert!(!not_modified(None, Some("Sat, 05 Nov 1994 00:00:00 GMT"), &headers));
+    }
+}
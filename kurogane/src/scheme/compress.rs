@@ -0,0 +1,99 @@
+//! On-the-fly gzip for app:// files on disk, with an in-memory cache.
+//!
+//! Only used when App::compress_responses is enabled. Entries are keyed by
+//! path and invalidated when the file's size or modification time changes.
+
+use std::collections::HashMap;
+use std::fs::Metadata;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+
+use flate2::Compression;
+use flate2::write::GzEncoder;
+
+use crate::mime;
+
+// Smaller files gain nothing from compression
+const MIN_SIZE: u64 = 1024;
+
+// Larger files are streamed from disk instead of read into memory
+const MAX_SIZE: u64 = 32 * 1024 * 1024;
+
+// Total compressed bytes kept in the cache
+const CACHE_BYTES: usize = 64 * 1024 * 1024;
+
+static CACHE: OnceLock<Mutex<Cache>> = OnceLock::new();
+
+#[derive(Default)]
+struct Cache {
+    entries: HashMap<PathBuf, Entry>,
+    bytes: usize,
+}
+
+struct Entry {
+    modified: Option<SystemTime>,
+    len: u64,
+    /// None when compression did not pay off; remembered so it isn't retried.
+    gzip: Option<Arc<[u8]>>,
+}
+
+fn cache() -> &'static Mutex<Cache> {
+    CACHE.get_or_init(|| Mutex::new(Cache::default()))
+}
+
+/// Gzipped contents of `path`, compressing and caching them on first use.
+/// None if the file is not worth compressing.
+pub(crate) fn gzip(path: &Path, meta: &Metadata, mime: &str) -> Option<Arc<[u8]>> {
+    if !mime::is_compressible(mime) || !(MIN_SIZE..=MAX_SIZE).contains(&meta.len()) {
+        return None;
+    }
+
+    let modified = meta.modified().ok();
+
+    if let Some(entry) = cache().lock().unwrap().entries.get(path)
+        && entry.modified == modified
+        && entry.len == meta.len()
+    {
+        return entry.gzip.clone();
+    }
+
+    // Compress without holding the lock; a concurrent miss just does it twice
+    let gzip = compress(path).map(Arc::from);
+
+    let mut cache = cache().lock().unwrap();
+    let size = gzip.as_ref().map_or(0, |g| g.len());
+
+    if let Some(old) = cache.entries.remove(path) {
+        cache.bytes -= old.gzip.map_or(0, |g| g.len());
+    }
+
+    // Evict arbitrary entries until the new one fits
+    while cache.bytes + size > CACHE_BYTES {
+        let Some(key) = cache.entries.keys().next().cloned() else { break };
+        let old = cache.entries.remove(&key).unwrap();
+        cache.bytes -= old.gzip.map_or(0, |g| g.len());
+    }
+
+    if size <= CACHE_BYTES {
+        cache.bytes += size;
+        cache.entries.insert(
+            path.to_path_buf(),
+            Entry { modified, len: meta.len(), gzip: gzip.clone() },
+        );
+    }
+
+    gzip
+}
+
+fn compress(path: &Path) -> Option<Vec<u8>> {
+    let data = std::fs::read(path).ok()?;
+
+    // Fast level: this runs while the page waits for the response
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+    encoder.write_all(&data).ok()?;
+    let compressed = encoder.finish().ok()?;
+
+    (compressed.len() < data.len() - data.len() / 8).then_some(compressed)
+}
@@ -0,0 +1,57 @@
+//! app:// serving options shared by every mount, collected by the App builder.
+//!
+//! Installed once in App::run before the runtime starts and read by the
+//! scheme handler for every request. Options that depend on the path, such
+//! as header rules or the SPA fallback, belong to each Mount instead.
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// Renders the HTML of an app:// error page from its status and URL.
+pub(crate) type ErrorRenderer = Box<dyn Fn(u16, &str) -> Option<String> + Send + Sync + 'static>;
+
+#[derive(Default)]
+pub(crate) struct Config {
+    /// Lowercase extension without the dot -> MIME type.
+    pub mime_types: HashMap<String, String>,
+    /// Gzip compressible files on disk that have no precompressed sibling.
+    pub compress: bool,
+    /// Send `no-store` and no validators, for development.
+    pub no_cache: bool,
+    /// Custom HTML for error replies; None falls back to the defaults.
+    pub error_renderer: Option<ErrorRenderer>,
+}
+
+impl Config {
+    /// MIME type for `path`: a user override by extension, else `builtin`.
+    pub fn mime_type(&self, path: &str, builtin: &str) -> String {
+        let ext = path
+            .rsplit('/')
+            .next()
+            .and_then(|name| name.rsplit_once('.'))
+            .map(|(_, ext)| ext.to_ascii_lowercase());
+
+        ext.and_then(|ext| self.mime_types.get(&ext))
+            .map_or(builtin, String::as_str)
+            .to_string()
+    }
+}
+
+/// Normalise a user-supplied extension: no leading dot, lowercase.
+pub(crate) fn extension_key(ext: &str) -> String {
+    ext.trim_start_matches('.').to_ascii_lowercase()
+}
+
+/// Install the configuration. Only the first call has an effect.
+pub(crate) fn set_config(config: Config) {
+    if CONFIG.set(config).is_err() {
+        eprintln!("[app://] scheme already configured, ignoring");
+    }
+}
+
+/// Active configuration, or the defaults if none was installed.
+pub(crate) fn config() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
@@ -0,0 +1,127 @@
+//! Path patterns for app:// configuration.
+//!
+//! `*` matches within one path segment, `**` across segments and `?` one
+//! character. Patterns and paths both start with `/`; a pattern without a
+//! leading slash is treated as if it had one.
+
+#[derive(Clone, Debug)]
+pub(crate) struct Glob {
+    pattern: String,
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Self {
+        let pattern = if pattern.starts_with('/') {
+            pattern.to_string()
+        } else {
+            format!("/{}", pattern)
+        };
+
+        Self { pattern }
+    }
+
+    /// Like new, but a trailing `/*` covers the whole subtree, so `/api/*`
+    /// also matches `/api/users/1`. Used where a pattern names a prefix.
+    pub fn subtree(pattern: &str) -> Self {
+        match pattern.strip_suffix("/*") {
+            Some(dir) => Self::new(&format!("{}/**", dir)),
+            None => Self::new(pattern),
+        }
+    }
+
+    /// Pattern over whole URLs, e.g. `https://*.example.com/**`; `*` stops at `/`.
+    pub fn url(pattern: &str) -> Self {
+        Self { pattern: pattern.to_string() }
+    }
+
+    pub fn matches(&self, path: &str) -> bool {
+        matches(self.pattern.as_bytes(), path.as_bytes())
+    }
+}
+
+fn matches(pattern: &[u8], path: &[u8]) -> bool {
+    match pattern {
+        [] => path.is_empty(),
+
+        // `/**/` also matches a single `/`, so `/a/**/b` matches `/a/b`
+        [b'/', b'*', b'*', b'/', ..] => {
+            matches(&pattern[3..], path)
+                || (path.first() == Some(&b'/') && matches(&pattern[1..], &path[1..]))
+        }
+
+        [b'*', b'*', rest @ ..] => (0..=path.len()).any(|i| matches(rest, &path[i..])),
+
+        [b'*', rest @ ..] => {
+            let segment = path.iter().position(|c| *c == b'/').unwrap_or(path.len());
+            (0..=segment).any(|i| matches(rest, &path[i..]))
+        }
+
+        [b'?', rest @ ..] => matches!(path, [c, ..] if *c != b'/') && matches(rest, &path[1..]),
+
+        [p, rest @ ..] => path.first() == Some(p) && matches(rest, &path[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn star_stays_in_one_segment() {
+        let glob = Glob::new("/assets/*.js");
+        assert!(glob.matches("/assets/app.js"));
+        assert!(glob.matches("/assets/.js"));
+        assert!(!glob.matches("/assets/lib/app.js"));
+        assert!(!glob.matches("/assets/app.css"));
+    }
+
+    #[test]
+    fn double_star_crosses_segments() {
+        let glob = Glob::new("/api/**");
+        assert!(glob.matches("/api/"));
+        assert!(glob.matches("/api/a/b/c"));
+        assert!(!glob.matches("/apix"));
+
+        let glob = Glob::new("a/**/b");
+        assert!(glob.matches("/a/b"));
+        assert!(glob.matches("/a/x/y/b"));
+        assert!(!glob.matches("/a/xb"));
+
+        assert!(Glob::new("/**").matches("/"));
+    }
+
+    #[test]
+    fn subtree_patterns_cover_nested_paths() {
+        let api = Glob::subtree("/api/*");
+        assert!(api.matches("/api/users"));
+        assert!(api.matches("/api/users/1"));
+        assert!(!api.matches("/apix/users"));
+
+        let assets = Glob::subtree("assets/*");
+        assert!(assets.matches("/assets/img/logo"));
+        assert!(!assets.matches("/settings/assets/x"));
+
+        assert!(Glob::subtree("/api/**").matches("/api/users/1"));
+        assert!(!Glob::subtree("/api/*.json").matches("/api/v1/a.json"));
+        assert!(!Glob::subtree("/settings").matches("/settings/profile"));
+    }
+
+    #[test]
+    fn question_mark_is_one_character() {
+        let glob = Glob::new("/img?.png");
+        assert!(glob.matches("/img1.png"));
+        assert!(!glob.matches("/img.png"));
+        assert!(!glob.matches("/img/.png"));
+    }
+
+    #[test]
+    fn url_patterns() {
+        let glob = Glob::url("https://*.example.com/**");
+        assert!(glob.matches("https://cdn.example.com/a/b.js"));
+        assert!(!glob.matches("https://example.com/"));
+        assert!(!glob.matches("https://evil.com/x.example.com/"));
+
+        assert!(Glob::url("*").matches("https:"));
+        assert!(!Glob::url("*").matches("https://a"));
+    }
+}
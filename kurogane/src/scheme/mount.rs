@@ -0,0 +1,328 @@
+//! Scheme/host pairs served by kurogane.
+//!
+//! The frontend is always mounted, at `app://app/` unless App::origin says
+//! otherwise. App::mount adds more, e.g. `media://library/` for user files.
+//! Mounts are installed in App::run, which executes in every CEF process,
+//! so custom schemes are registered consistently everywhere.
+
+use std::ops::BitOr;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use cef::sys::cef_scheme_options_t::*;
+
+use crate::{Archive, EmbeddedAssets};
+use crate::error::RuntimeError;
+use super::glob::Glob;
+use super::proxy::Upstream;
+use super::route::{RouteHandler, SchemeRequest, SchemeResponse};
+
+static MOUNTS: OnceLock<Vec<Mount>> = OnceLock::new();
+
+/// One scheme/host pair with its own files and routes.
+///
+/// ```ignore
+/// App::new("demo").mount(
+///     Mount::new("media", "library")
+///         .dir(dirs::audio_dir().unwrap())
+///         .flags(SchemeFlags::STANDARD | SchemeFlags::SECURE | SchemeFlags::FETCH_ENABLED),
+/// )
+/// ```
+///
+/// Header rules, caching, the SPA fallback, the 404 page and the dotfile
+/// and symlink policies belong to each mount. The matching App methods
+/// configure the frontend mount; MIME types, compression and error pages
+/// configured on App apply to every mount.
+pub struct Mount {
+    pub(crate) scheme: String,
+    pub(crate) host: String,
+    pub(crate) source: Option<MountSource>,
+    pub(crate) routes: Vec<(Glob, RouteHandler)>,
+    pub(crate) flags: SchemeFlags,
+    /// Extra response headers for paths matching a pattern, in the order added.
+    pub(crate) header_rules: Vec<(Glob, Vec<(String, String)>)>,
+    /// Serve index.html for unmatched extension-less paths.
+    pub(crate) spa_fallback: bool,
+    pub(crate) spa_exclude: Vec<Glob>,
+    /// Cache-Control values for paths matching a pattern; the last match wins.
+    pub(crate) cache_rules: Vec<(Glob, String)>,
+    /// File in the mount served with status 404 for missing paths.
+    pub(crate) not_found_page: Option<String>,
+    /// Serve files and directories whose name starts with a dot.
+    pub(crate) dotfiles: bool,
+    /// Follow symlinks that resolve outside the mount's directory.
+    pub(crate) external_symlinks: bool,
+}
+
+/// Where a mount's files come from.
+pub(crate) enum MountSource {
+    Dir(PathBuf),
+    Embedded(&'static EmbeddedAssets),
+    Archive(Archive),
+    /// Base URL of a dev server that receives every request.
+    Proxy(String),
+}
+
+/// Options a custom scheme is registered with; see CEF's `cef_scheme_options_t`.
+///
+/// The default, `STANDARD | SECURE | CORS_ENABLED | FETCH_ENABLED`, makes a
+/// scheme behave like https for the page.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SchemeFlags(i32);
+
+impl SchemeFlags {
+    /// Standard URL semantics: `scheme://host/path`, relative URLs, origins.
+    pub const STANDARD: Self = Self(CEF_SCHEME_OPTION_STANDARD as i32);
+    /// Only pages of the same scheme may load it, like file://.
+    pub const LOCAL: Self = Self(CEF_SCHEME_OPTION_LOCAL as i32);
+    /// Only pages of the same scheme may display or link to it.
+    pub const DISPLAY_ISOLATED: Self = Self(CEF_SCHEME_OPTION_DISPLAY_ISOLATED as i32);
+    /// Treated as a secure context, like https.
+    pub const SECURE: Self = Self(CEF_SCHEME_OPTION_SECURE as i32);
+    /// May be the target of CORS requests.
+    pub const CORS_ENABLED: Self = Self(CEF_SCHEME_OPTION_CORS_ENABLED as i32);
+    /// Bypasses the page's Content-Security-Policy.
+    pub const CSP_BYPASSING: Self = Self(CEF_SCHEME_OPTION_CSP_BYPASSING as i32);
+    /// Reachable with the Fetch API.
+    pub const FETCH_ENABLED: Self = Self(CEF_SCHEME_OPTION_FETCH_ENABLED as i32);
+
+    pub(crate) fn bits(self) -> i32 {
+        self.0
+    }
+}
+
+impl Default for SchemeFlags {
+    fn default() -> Self {
+        Self::STANDARD | Self::SECURE | Self::CORS_ENABLED | Self::FETCH_ENABLED
+    }
+}
+
+impl BitOr for SchemeFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl Mount {
+    /// Mount with no files or routes yet. Scheme and host are lowercased,
+    /// as Chromium does for standard URLs.
+    pub fn new(scheme: impl Into<String>, host: impl Into<String>) -> Self {
+        Self {
+            scheme: scheme.into().to_ascii_lowercase(),
+            host: host.into().to_ascii_lowercase(),
+            source: None,
+            routes: Vec::new(),
+            flags: SchemeFlags::default(),
+            header_rules: Vec::new(),
+            spa_fallback: false,
+            spa_exclude: Vec::new(),
+            cache_rules: Vec::new(),
+            not_found_page: None,
+            dotfiles: false,
+            external_symlinks: false,
+        }
+    }
+
+    /// Serve files from a directory.
+    pub fn dir(mut self, path: impl Into<PathBuf>) -> Self {
+        self.source = Some(MountSource::Dir(path.into()));
+        self
+    }
+
+    /// Serve files embedded with `include_assets!`.
+    pub fn embedded(mut self, assets: &'static EmbeddedAssets) -> Self {
+        self.source = Some(MountSource::Embedded(assets));
+        self
+    }
+
+    /// Serve files from a packed archive.
+    ///
+    /// ```ignore
+    /// Mount::new("app", "docs").archive(Archive::open("docs.kpak")?)
+    /// ```
+    pub fn archive(mut self, archive: Archive) -> Self {
+        self.source = Some(MountSource::Archive(archive));
+        self
+    }
+
+    /// Forward requests to a dev server, e.g. `http://localhost:5173`,
+    /// instead of serving files. Routes still take priority.
+    pub fn proxy(mut self, url: impl Into<String>) -> Self {
+        self.source = Some(MountSource::Proxy(url.into()));
+        self
+    }
+
+    /// Answer paths matching `pattern` from Rust; see App::route.
+    pub fn route<F>(mut self, pattern: &str, handler: F) -> Self
+    where
+        F: Fn(SchemeRequest) -> SchemeResponse + Send + Sync + 'static,
+    {
+        self.routes.push((Glob::new(pattern), Box::new(handler)));
+        self
+    }
+
+    /// Register the scheme with these options instead of the defaults.
+    /// Mounts sharing a scheme are registered with the union of their flags.
+    pub fn flags(mut self, flags: SchemeFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Add response headers to paths matching `pattern`; see App::header_rule.
+    pub fn header_rule<I, K, V>(mut self, pattern: &str, headers: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let headers = headers
+            .into_iter()
+            .map(|(k, v)| (k.into(), v.into()))
+            .collect();
+
+        self.header_rules.push((Glob::new(pattern), headers));
+        self
+    }
+
+    /// Send the COOP/COEP headers on every response; see App::cross_origin_isolated.
+    pub fn cross_origin_isolated(self) -> Self {
+        self.header_rule(
+            "/**",
+            [
+                ("Cross-Origin-Opener-Policy", "same-origin"),
+                ("Cross-Origin-Embedder-Policy", "require-corp"),
+                ("Cross-Origin-Resource-Policy", "same-origin"),
+            ],
+        )
+    }
+
+    /// Answer missing extension-less paths with index.html; see App::spa_fallback.
+    pub fn spa_fallback(mut self) -> Self {
+        self.spa_fallback = true;
+        self
+    }
+
+    /// Keep paths matching `pattern` out of the SPA fallback; a trailing
+    /// `/*` covers the whole subtree.
+    pub fn spa_fallback_exclude(mut self, pattern: &str) -> Self {
+        self.spa_exclude.push(Glob::subtree(pattern));
+        self
+    }
+
+    /// Set Cache-Control for paths matching `pattern`; see App::cache_control.
+    pub fn cache_control(mut self, pattern: &str, value: impl Into<String>) -> Self {
+        self.cache_rules.push((Glob::new(pattern), value.into()));
+        self
+    }
+
+    /// Serve this file of the mount with status 404 when a path matches nothing.
+    pub fn not_found_page(mut self, path: impl Into<String>) -> Self {
+        self.not_found_page = Some(path.into().trim_start_matches('/').to_string());
+        self
+    }
+
+    /// Serve files and directories whose name starts with a dot.
+    pub fn allow_dotfiles(mut self) -> Self {
+        self.dotfiles = true;
+        self
+    }
+
+    /// Follow symlinks that point outside the mount's directory.
+    pub fn follow_external_symlinks(mut self) -> Self {
+        self.external_symlinks = true;
+        self
+    }
+
+    /// Handler for `path`, if any; routes are tried in the order added.
+    pub(crate) fn handler_for(&self, path: &str) -> Option<&RouteHandler> {
+        let path = format!("/{}", path);
+
+        self.routes
+            .iter()
+            .find(|(glob, _)| glob.matches(&path))
+            .map(|(_, handler)| handler)
+    }
+
+    /// Cache-Control for `path`. Without a rule, assets are revalidated on
+    /// every use, which is cheap since unchanged ones come back as 304.
+    pub(crate) fn cache_control_for(&self, path: &str) -> &str {
+        let path = format!("/{}", path);
+
+        self.cache_rules
+            .iter()
+            .rev()
+            .find(|(glob, _)| glob.matches(&path))
+            .map_or("no-cache", |(_, value)| value.as_str())
+    }
+
+    /// Whether a missing `path` should be answered with index.html.
+    pub(crate) fn falls_back(&self, path: &str) -> bool {
+        let name = path.rsplit('/').next().unwrap_or(path);
+        let path = format!("/{}", path);
+
+        self.spa_fallback
+            && !name.contains('.')
+            && !self.spa_exclude.iter().any(|glob| glob.matches(&path))
+    }
+
+    /// Add the headers of every rule matching `path` to `headers`.
+    /// A later rule replaces a header of the same name set earlier.
+    pub(crate) fn apply_header_rules(&self, path: &str, headers: &mut Vec<(String, String)>) {
+        let path = format!("/{}", path);
+
+        for (glob, rule) in &self.header_rules {
+            if !glob.matches(&path) {
+                continue;
+            }
+
+            for (name, value) in rule {
+                headers.retain(|(n, _)| !n.eq_ignore_ascii_case(name));
+                headers.push((name.clone(), value.clone()));
+            }
+        }
+    }
+
+    /// `scheme://host/`, the prefix of every URL this mount serves.
+    pub(crate) fn origin(&self) -> String {
+        format!("{}://{}/", self.scheme, self.host)
+    }
+
+    /// Whether the scheme is built into Chromium and must not be registered.
+    pub(crate) fn is_builtin_scheme(&self) -> bool {
+        matches!(self.scheme.as_str(), "http" | "https")
+    }
+
+    /// Canonicalize the directory, so lookups can check they stay inside it,
+    /// and check a dev proxy target is usable.
+    pub(crate) fn canonicalize(mut self) -> Result<Self, RuntimeError> {
+        match &self.source {
+            Some(MountSource::Dir(path)) => {
+                let canonical = path
+                    .canonicalize()
+                    .map_err(|_| RuntimeError::AssetRootMissing(path.clone()))?;
+
+                self.source = Some(MountSource::Dir(canonical));
+            }
+            Some(MountSource::Proxy(url)) => {
+                Upstream::parse(url).map_err(RuntimeError::DevProxyInvalid)?;
+            }
+            _ => {}
+        }
+
+        Ok(self)
+    }
+}
+
+/// Install the mounts. Only the first call has an effect.
+pub(crate) fn set_mounts(mounts: Vec<Mount>) {
+    if MOUNTS.set(mounts).is_err() {
+        eprintln!("[app://] mounts already configured, ignoring");
+    }
+}
+
+/// Installed mounts; the frontend mount comes first.
+pub(crate) fn mounts() -> &'static [Mount] {
+    MOUNTS.get().map_or(&[], Vec::as_slice)
+}
@@ -0,0 +1,330 @@
+//! Dev proxy: forward app:// requests to a local dev server.
+//!
+//! Keeps the page on its production origin during development, so storage,
+//! CSP, cookies and IPC checks behave the same as in a release build.
+//! Speaks plain HTTP/1.1 over one connection per request; responses are
+//! streamed back as they arrive.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+// A dev server silent for this long has stalled. Streams that stay idle
+// longer, such as quiet event streams, end and are reconnected by the page.
+const IO_TIMEOUT: Duration = Duration::from_secs(60);
+
+// Headers that describe one connection, not the message
+const HOP_BY_HOP: &[&str] = &[
+    "connection", "keep-alive", "proxy-connection", "te", "trailer",
+    "transfer-encoding", "upgrade", "host", "content-length",
+];
+
+/// Where a mount's requests are forwarded, e.g. `http://localhost:5173`.
+pub(crate) struct Upstream {
+    /// `http://host:port`, for rewriting redirects.
+    origin: String,
+    /// Host name or address, without the brackets of an IPv6 literal.
+    host: String,
+    port: u16,
+    /// Path prefix without a trailing slash, usually empty.
+    base: String,
+}
+
+/// A response from the dev server, body not yet read.
+pub(crate) struct Proxied {
+    pub status: i32,
+    pub mime: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Box<dyn Read + Send>,
+}
+
+impl Upstream {
+    pub fn parse(url: &str) -> Result<Self, String> {
+        let rest = url
+            .strip_prefix("http://")
+            .ok_or_else(|| format!("dev proxy target {} must be an http:// URL", url))?;
+
+        let (authority, base) = rest.split_once('/').unwrap_or((rest, ""));
+
+        // IPv6 literals are bracketed, e.g. [::1]:5173
+        let (host, port) = match authority.strip_prefix('[') {
+            Some(bracketed) => bracketed
+                .split_once(']')
+                .ok_or_else(|| format!("unclosed [ in dev proxy target {}", url))?,
+            None => match authority.rsplit_once(':') {
+                Some((host, port)) => (host, port),
+                None => (authority, ""),
+            },
+        };
+
+        let port = match port {
+            "" | ":" => 80,
+            port => port
+                .trim_start_matches(':')
+                .parse()
+                .map_err(|_| format!("invalid port in dev proxy target {}", url))?,
+        };
+
+        if host.is_empty() {
+            return Err(format!("dev proxy target {} has no host", url));
+        }
+
+        Ok(Self {
+            origin: format!("http://{}", authority),
+            host: host.to_string(),
+            port,
+            base: format!("/{}", base.trim_end_matches('/')).trim_end_matches('/').to_string(),
+        })
+    }
+
+    pub fn url(&self) -> String {
+        format!("{}{}", self.origin, self.base)
+    }
+
+    /// Send one request. `target` is the path and query relative to the
+    /// mount, without a leading slash.
+    pub fn forward(
+        &self,
+        method: &str,
+        target: &str,
+        headers: &[(String, String)],
+        body: &[u8],
+    ) -> io::Result<Proxied> {
+        let mut stream = self.connect()?;
+        stream.set_read_timeout(Some(IO_TIMEOUT))?;
+        stream.set_write_timeout(Some(IO_TIMEOUT))?;
+
+        let mut head = format!("{} {}/{} HTTP/1.1\r\n", method, self.base, target);
+        head += &format!("Host: {}\r\n", self.authority());
+        head += "Connection: close\r\n";
+
+        for (name, value) in headers {
+            if !is_hop_by_hop(name) {
+                head += &format!("{}: {}\r\n", name, value);
+            }
+        }
+
+        if !body.is_empty() {
+            head += &format!("Content-Length: {}\r\n", body.len());
+        }
+
+        head += "\r\n";
+
+        stream.write_all(head.as_bytes())?;
+        stream.write_all(body)?;
+
+        read_response(BufReader::new(stream), method == "HEAD")
+    }
+
+    /// Connect to the first address that accepts. `localhost` resolves to
+    /// `::1` and `127.0.0.1`, and dev servers often listen on only one.
+    fn connect(&self) -> io::Result<TcpStream> {
+        let mut last = io::Error::new(io::ErrorKind::NotFound, "dev server host did not resolve");
+
+        for addr in (self.host.as_str(), self.port).to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+                Ok(stream) => return Ok(stream),
+                Err(e) => last = e,
+            }
+        }
+
+        Err(last)
+    }
+
+    /// `host:port` as written in a URL or Host header.
+    fn authority(&self) -> String {
+        if self.host.contains(':') {
+            format!("[{}]:{}", self.host, self.port)
+        } else {
+            format!("{}:{}", self.host, self.port)
+        }
+    }
+
+    /// Map a redirect to the dev server back onto `origin` (`scheme://host/`).
+    pub fn rewrite_location(&self, location: &str, origin: &str) -> String {
+        let prefix = format!("{}/", self.url());
+
+        match location.strip_prefix(&prefix) {
+            Some(rest) => format!("{}{}", origin, rest),
+            None => match location.strip_prefix(&format!("{}/", self.base)) {
+                // Absolute path on the dev server
+                Some(rest) if location.starts_with('/') => format!("{}{}", origin, rest),
+                _ => location.to_string(),
+            },
+        }
+    }
+}
+
+fn is_hop_by_hop(name: &str) -> bool {
+    HOP_BY_HOP.iter().any(|h| name.eq_ignore_ascii_case(h))
+}
+
+fn read_response(mut reader: BufReader<TcpStream>, head_only: bool) -> io::Result<Proxied> {
+    let invalid = |what: &str| io::Error::new(io::ErrorKind::InvalidData, format!("dev server sent {}", what));
+
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+
+    // "HTTP/1.1 200 OK"
+    let status: i32 = line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| invalid("a malformed status line"))?;
+
+    let mut mime = String::from("application/octet-stream");
+    let mut headers = Vec::new();
+    let mut length = None;
+    let mut chunked = false;
+
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(invalid("truncated headers"));
+        }
+
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+
+        let Some((name, value)) = line.split_once(':') else {
+            return Err(invalid("a malformed header"));
+        };
+        let (name, value) = (name.trim(), value.trim());
+
+        if name.eq_ignore_ascii_case("Content-Type") {
+            mime = value.to_string();
+        } else if name.eq_ignore_ascii_case("Content-Length") {
+            length = value.parse::<u64>().ok();
+        } else if name.eq_ignore_ascii_case("Transfer-Encoding") {
+            chunked = value.to_ascii_lowercase().contains("chunked");
+        } else if !is_hop_by_hop(name) {
+            headers.push((name.to_string(), value.to_string()));
+        }
+    }
+
+    let no_body = head_only || status == 204 || status == 304 || (100..200).contains(&status);
+
+    let body: Box<dyn Read + Send> = if no_body {
+        Box::new(io::empty())
+    } else if chunked {
+        Box::new(Chunked { inner: reader, remaining: 0, done: false })
+    } else if let Some(length) = length {
+        Box::new(reader.take(length))
+    } else {
+        // Connection: close delimits the body
+        Box::new(reader)
+    };
+
+    Ok(Proxied { status, mime, headers, body })
+}
+
+/// Decodes a `Transfer-Encoding: chunked` body.
+struct Chunked {
+    inner: BufReader<TcpStream>,
+    remaining: u64,
+    done: bool,
+}
+
+impl Read for Chunked {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if self.done || out.is_empty() {
+            return Ok(0);
+        }
+
+        if self.remaining == 0 {
+            let mut line = String::new();
+            self.inner.read_line(&mut line)?;
+
+            // Chunk extensions follow a ';'
+            let size = line.trim().split(';').next().unwrap_or("");
+            self.remaining = u64::from_str_radix(size, 16)
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "bad chunk size"))?;
+
+            if self.remaining == 0 {
+                // Trailers are ignored
+                self.done = true;
+                return Ok(0);
+            }
+        }
+
+        let want = self.remaining.min(out.len() as u64) as usize;
+        let n = self.inner.read(&mut out[..want])?;
+
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        self.remaining -= n as u64;
+
+        // Each chunk ends with CRLF
+        if self.remaining == 0 {
+            let mut crlf = [0; 2];
+            self.inner.read_exact(&mut crlf)?;
+        }
+
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parts(url: &str) -> (String, u16, String, String) {
+        let u = Upstream::parse(url).unwrap();
+        (u.host.clone(), u.port, u.base.clone(), u.authority())
+    }
+
+    #[test]
+    fn parses_targets() {
+        assert_eq!(
+            parts("http://localhost:5173"),
+            ("localhost".into(), 5173, "".into(), "localhost:5173".into())
+        );
+        assert_eq!(
+            parts("http://127.0.0.1/app/"),
+            ("127.0.0.1".into(), 80, "/app".into(), "127.0.0.1:80".into())
+        );
+    }
+
+    #[test]
+    fn parses_ipv6_literals() {
+        assert_eq!(parts("http://[::1]:5173"), ("::1".into(), 5173, "".into(), "[::1]:5173".into()));
+        assert_eq!(parts("http://[fe80::1]/base"), ("fe80::1".into(), 80, "/base".into(), "[fe80::1]:80".into()));
+
+        let u = Upstream::parse("http://[::1]:5173").unwrap();
+        assert!((u.host.as_str(), u.port).to_socket_addrs().is_ok());
+        assert_eq!(u.url(), "http://[::1]:5173");
+    }
+
+    #[test]
+    fn rejects_bad_targets() {
+        for url in ["https://localhost", "http://", "http://:80", "http://host:x", "http://[::1", "http://[::1]x"] {
+            assert!(Upstream::parse(url).is_err(), "{} accepted", url);
+        }
+    }
+
+    #[test]
+    fn tries_every_resolved_address() {
+        use std::net::TcpListener;
+
+        // Only IPv4 listens; localhost may resolve to ::1 first
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let u = Upstream::parse(&format!("http://localhost:{}", port)).unwrap();
+        assert!(u.connect().is_ok());
+    }
+
+    #[test]
+    fn rewrites_redirects() {
+        let u = Upstream::parse("http://[::1]:5173").unwrap();
+        assert_eq!(u.rewrite_location("http://[::1]:5173/a?b", "app://app/"), "app://app/a?b");
+        assert_eq!(u.rewrite_location("/a", "app://app/"), "app://app/a");
+        assert_eq!(u.rewrite_location("https://example.com/", "app://app/"), "https://example.com/");
+    }
+}
@@ -0,0 +1,153 @@
+//! Rust handlers for app:// paths, registered with App::route.
+//!
+//! Handlers run on the app:// IO workers, so they may block, and take
+//! priority over file lookup.
+
+use std::io::Read;
+
+use serde_json::Value;
+
+use super::url::percent_decode;
+
+pub(crate) type RouteHandler =
+    Box<dyn Fn(SchemeRequest) -> SchemeResponse + Send + Sync + 'static>;
+
+/// A request to a routed app:// path, as seen by a route handler.
+pub struct SchemeRequest {
+    pub(crate) method: String,
+    pub(crate) url: String,
+    pub(crate) path: String,
+    pub(crate) query: String,
+    pub(crate) headers: Vec<(String, String)>,
+    pub(crate) body: Vec<u8>,
+}
+
+impl SchemeRequest {
+    /// HTTP method, e.g. "GET" or "POST".
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Path with a leading slash, e.g. "/api/thumbnail".
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Raw query string without the `?`, e.g. "id=3&size=64".
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    /// First value of a query parameter. `+` and percent escapes are decoded.
+    pub fn query_param(&self, name: &str) -> Option<String> {
+        self.query
+            .split('&')
+            .filter_map(|pair| {
+                let (k, v) = pair.split_once('=').unwrap_or((pair, ""));
+                (decode_component(k) == name).then(|| decode_component(v))
+            })
+            .next()
+    }
+
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+
+    /// First value of a header, matched case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Request body, e.g. from `fetch(url, { method: "POST", body })`.
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+}
+
+/// What a route handler sends back.
+pub struct SchemeResponse {
+    pub(crate) status: u16,
+    pub(crate) mime: String,
+    pub(crate) headers: Vec<(String, String)>,
+    pub(crate) body: ResponseBody,
+}
+
+pub(crate) enum ResponseBody {
+    Buffered(Vec<u8>),
+    Stream(Box<dyn Read + Send>),
+}
+
+impl SchemeResponse {
+    /// Empty response with the given status.
+    pub fn new(status: u16) -> Self {
+        Self::bytes("text/plain; charset=utf-8", Vec::new()).status(status)
+    }
+
+    /// 200 with a buffered body of type `mime`.
+    pub fn bytes(mime: impl Into<String>, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status: 200,
+            mime: mime.into(),
+            headers: Vec::new(),
+            body: ResponseBody::Buffered(body.into()),
+        }
+    }
+
+    pub fn text(body: impl Into<String>) -> Self {
+        Self::bytes("text/plain; charset=utf-8", body.into())
+    }
+
+    pub fn html(body: impl Into<String>) -> Self {
+        Self::bytes("text/html; charset=utf-8", body.into())
+    }
+
+    pub fn json(value: &Value) -> Self {
+        Self::bytes("application/json; charset=utf-8", value.to_string())
+    }
+
+    /// 200 whose body is read from `reader` in chunks as the page consumes it,
+    /// for large or generated content. No Content-Length is sent.
+    pub fn stream(mime: impl Into<String>, reader: impl Read + Send + 'static) -> Self {
+        Self {
+            status: 200,
+            mime: mime.into(),
+            headers: Vec::new(),
+            body: ResponseBody::Stream(Box::new(reader)),
+        }
+    }
+
+    pub fn not_found() -> Self {
+        Self::text("404 Not Found").status(404)
+    }
+
+    pub fn status(mut self, status: u16) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Add a header. `Content-Type` replaces the MIME type.
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        let (name, value) = (name.into(), value.into());
+
+        if name.eq_ignore_ascii_case("Content-Type") {
+            self.mime = value;
+        } else {
+            self.headers.push((name, value));
+        }
+
+        self
+    }
+}
+
+/// Decode `+` and `%XX` escapes of a query component; invalid UTF-8 is replaced.
+fn decode_component(s: &str) -> String {
+    let plus_decoded = s.replace('+', " ");
+    String::from_utf8_lossy(&percent_decode(plus_decoded.as_bytes())).into_owned()
+}
@@ -0,0 +1,151 @@
+//! Request URL parsing for app://.
+//!
+//! Splits off the fragment and query, strips the mount's origin and
+//! percent-decodes the path one segment at a time. Paths that could
+//! address anything outside the mount are rejected rather than cleaned up.
+
+/// A request path relative to its mount.
+pub(crate) struct ParsedUrl {
+    /// Decoded path without leading or trailing slashes, e.g. "docs/my file.png".
+    pub path: String,
+    /// Raw query string without the `?`.
+    pub query: String,
+    /// The URL ended in `/` (or named the root), so it asks for a directory.
+    pub dir: bool,
+}
+
+/// Parse `url`, which must start with `origin` (`scheme://host/`).
+pub(crate) fn parse(url: &str, origin: &str) -> Result<ParsedUrl, String> {
+    let url = url.split('#').next().unwrap_or_default();
+    let (url, query) = url.split_once('?').unwrap_or((url, ""));
+
+    // Chromium canonicalizes scheme and host of standard URLs to lowercase
+    let rest = match url.get(..origin.len()) {
+        Some(prefix) if prefix.eq_ignore_ascii_case(origin) => &url[origin.len()..],
+        // app://app without the trailing slash
+        _ if url.eq_ignore_ascii_case(origin.trim_end_matches('/')) => "",
+        _ => return Err(format!("{} is not under {}", url, origin)),
+    };
+
+    let dir = rest.is_empty() || rest.ends_with('/');
+    let mut segments = Vec::new();
+
+    for raw in rest.split('/').filter(|s| !s.is_empty()) {
+        let segment = String::from_utf8(percent_decode(raw.as_bytes()))
+            .map_err(|_| format!("path segment {} is not UTF-8", raw))?;
+
+        // Encoded separators and dot segments would escape the segment, and
+        // a drive prefix like `C:` makes Path::join discard the root on Windows
+        if segment == "." || segment == ".." || segment.contains(['/', '\\', '\0', ':']) {
+            return Err(format!("invalid path segment {}", raw));
+        }
+
+        segments.push(segment);
+    }
+
+    Ok(ParsedUrl {
+        path: segments.join("/"),
+        query: query.to_string(),
+        dir,
+    })
+}
+
+/// Decode `%XX` escapes. Malformed escapes are kept as-is, as browsers do.
+pub(crate) fn percent_decode(bytes: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let escaped = match bytes.get(i..i + 3) {
+            Some([b'%', hi, lo]) => hex(*hi).zip(hex(*lo)).map(|(hi, lo)| hi << 4 | lo),
+            _ => None,
+        };
+
+        match escaped {
+            Some(b) => {
+                out.push(b);
+                i += 3;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    out
+}
+
+fn hex(c: u8) -> Option<u8> {
+    (c as char).to_digit(16).map(|d| d as u8)
+}
+
+/// Whether any segment of `path` is hidden, like `.env` or `.git/config`.
+pub(crate) fn has_dotfile(path: &str) -> bool {
+    path.split('/').any(|s| s.starts_with('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORIGIN: &str = "app://app/";
+
+    fn path(url: &str) -> Result<String, String> {
+        parse(url, ORIGIN).map(|p| p.path)
+    }
+
+    #[test]
+    fn splits_query_fragment_and_directories() {
+        let p = parse("app://app/docs/?q=1#top", ORIGIN).unwrap();
+        assert_eq!((p.path.as_str(), p.query.as_str(), p.dir), ("docs", "q=1", true));
+
+        let p = parse("APP://App/index.html#a?b", ORIGIN).unwrap();
+        assert_eq!((p.path.as_str(), p.query.as_str(), p.dir), ("index.html", "", false));
+
+        assert!(parse("app://app", ORIGIN).unwrap().dir);
+        assert!(parse("app://other/x", ORIGIN).is_err());
+    }
+
+    #[test]
+    fn decodes_each_segment() {
+        assert_eq!(path("app://app/my%20file.png").unwrap(), "my file.png");
+        assert_eq!(path("app://app/%E6%97%A5.txt").unwrap(), "日.txt");
+        assert_eq!(path("app://app//a///b").unwrap(), "a/b");
+
+        // Malformed escapes stay as they are
+        assert_eq!(path("app://app/100%.txt").unwrap(), "100%.txt");
+        assert_eq!(path("app://app/%zz%4").unwrap(), "%zz%4");
+
+        assert!(path("app://app/%FF").is_err());
+    }
+
+    #[test]
+    fn rejects_segments_that_escape() {
+        for url in [
+            "app://app/.",
+            "app://app/..",
+            "app://app/a/../b",
+            "app://app/%2e%2e/secret",
+            "app://app/%2E",
+            "app://app/a%2Fb",
+            "app://app/a%5Cb",
+            "app://app/a%00",
+            "app://app/C:",
+            "app://app/c%3A/Windows",
+            "app://app/file.txt:stream",
+        ] {
+            assert!(path(url).is_err(), "{} accepted", url);
+        }
+
+        // Dots inside a name are fine
+        assert_eq!(path("app://app/..hidden/a..b").unwrap(), "..hidden/a..b");
+    }
+
+    #[test]
+    fn finds_dotfiles() {
+        assert!(has_dotfile(".env"));
+        assert!(has_dotfile("a/.git/config"));
+        assert!(!has_dotfile("a/b.c/d"));
+    }
+}
@@ -0,0 +1,168 @@
+//! Typed binary responses.
+//!
+//! A binary command may return a TypedBuffer instead of raw bytes.
+//! The element type and shape travel with the response so the bridge can
+//! hand JS the matching typed array (Float32Array, Int16Array, ...) directly.
+//!
+//! The typed constructors return a Result so a handler can return them as
+//! is; a shape that doesn't fit the data rejects the JS call.
+
+/// Element type of a TypedBuffer, mirroring the JS typed array family.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DType {
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+    U64,
+    I64,
+    F32,
+    F64,
+}
+
+impl DType {
+    /// Wire name understood by the bridge.
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            DType::U8 => "u8",
+            DType::I8 => "i8",
+            DType::U16 => "u16",
+            DType::I16 => "i16",
+            DType::U32 => "u32",
+            DType::I32 => "i32",
+            DType::U64 => "u64",
+            DType::I64 => "i64",
+            DType::F32 => "f32",
+            DType::F64 => "f64",
+        }
+    }
+
+    /// Inverse of as_str, for tags received over IPC.
+    pub(crate) fn from_wire(s: &str) -> Option<Self> {
+        Some(match s {
+            "u8" => DType::U8,
+            "i8" => DType::I8,
+            "u16" => DType::U16,
+            "i16" => DType::I16,
+            "u32" => DType::U32,
+            "i32" => DType::I32,
+            "u64" => DType::U64,
+            "i64" => DType::I64,
+            "f32" => DType::F32,
+            "f64" => DType::F64,
+            _ => return None,
+        })
+    }
+
+    /// Element size in bytes.
+    pub(crate) fn size(self) -> usize {
+        match self {
+            DType::U8 | DType::I8 => 1,
+            DType::U16 | DType::I16 => 2,
+            DType::U32 | DType::I32 | DType::F32 => 4,
+            DType::U64 | DType::I64 | DType::F64 => 8,
+        }
+    }
+}
+
+/// Bytes returned by a binary command, optionally tagged with dtype and shape.
+///
+/// Untagged buffers (plain `Vec<u8>`) resolve to an ArrayBuffer in JS.
+/// Tagged buffers resolve to the matching typed array with a `shape` property.
+pub struct TypedBuffer {
+    pub(crate) bytes: Vec<u8>,
+    pub(crate) tag: Option<(DType, Vec<usize>)>,
+}
+
+macro_rules! typed_constructor {
+    ($name:ident, $ty:ty, $dtype:expr) => {
+        #[doc = concat!("Tag a `Vec<", stringify!($ty), ">` with its shape. An empty shape means one dimension.")]
+        ///
+        /// Errors if the shape describes a different number of elements.
+        pub fn $name(data: Vec<$ty>, shape: impl Into<Vec<usize>>) -> Result<Self, String> {
+            let shape = Self::check_shape(data.len(), shape.into())?;
+            // JS typed arrays use platform byte order, same as the renderer
+            let bytes = data.iter().flat_map(|v| v.to_ne_bytes()).collect();
+
+            Ok(Self { bytes, tag: Some(($dtype, shape)) })
+        }
+    };
+}
+
+impl TypedBuffer {
+    typed_constructor!(u8, u8, DType::U8);
+    typed_constructor!(i8, i8, DType::I8);
+    typed_constructor!(u16, u16, DType::U16);
+    typed_constructor!(i16, i16, DType::I16);
+    typed_constructor!(u32, u32, DType::U32);
+    typed_constructor!(i32, i32, DType::I32);
+    typed_constructor!(u64, u64, DType::U64);
+    typed_constructor!(i64, i64, DType::I64);
+    typed_constructor!(f32, f32, DType::F32);
+    typed_constructor!(f64, f64, DType::F64);
+
+    /// Raw bytes without type information; resolves to an ArrayBuffer.
+    pub fn raw(bytes: Vec<u8>) -> Self {
+        Self { bytes, tag: None }
+    }
+
+    pub fn dtype(&self) -> Option<DType> {
+        self.tag.as_ref().map(|(d, _)| *d)
+    }
+
+    pub fn shape(&self) -> Option<&[usize]> {
+        self.tag.as_ref().map(|(_, s)| s.as_slice())
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    fn check_shape(len: usize, shape: Vec<usize>) -> Result<Vec<usize>, String> {
+        if shape.is_empty() {
+            return Ok(vec![len]);
+        }
+
+        let elements = shape.iter().try_fold(1usize, |n, d| n.checked_mul(*d));
+
+        if elements != Some(len) {
+            return Err(format!(
+                "TypedBuffer shape {:?} does not match the {} elements of its data",
+                shape, len
+            ));
+        }
+
+        Ok(shape)
+    }
+}
+
+impl From<Vec<u8>> for TypedBuffer {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::raw(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_matching_shapes() {
+        let t = TypedBuffer::f32(vec![0.0; 6], [2, 3]).unwrap();
+        assert_eq!(t.shape(), Some(&[2, 3][..]));
+        assert_eq!(t.as_bytes().len(), 24);
+
+        let t = TypedBuffer::u16(vec![1, 2, 3], []).unwrap();
+        assert_eq!(t.shape(), Some(&[3][..]));
+
+        assert!(TypedBuffer::i8(Vec::new(), [0, 5]).is_ok());
+    }
+
+    #[test]
+    fn rejects_mismatched_shapes() {
+        assert!(TypedBuffer::f64(vec![0.0; 6], [4, 2]).is_err());
+        assert!(TypedBuffer::u8(vec![0; 4], [usize::MAX, 2]).is_err());
+    }
+}
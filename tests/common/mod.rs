@@ -0,0 +1,39 @@
+//! Helpers shared by the test applications.
+
+// Each test uses only some of them
+#![allow(dead_code)]
+
+use serde_json::Value;
+
+/// `report` command: print the results the page collected and exit,
+/// non-zero if any case failed.
+///
+/// Each result is `{ name, url, ok, detail }`.
+pub fn report(results: Value) -> Result<Value, String> {
+    let results = results.as_array().cloned().unwrap_or_default();
+    let mut failed = 0;
+
+    for r in &results {
+        let ok = r["ok"].as_bool().unwrap_or(false);
+        if !ok {
+            failed += 1;
+        }
+
+        println!(
+            "[{}] {} ({} -> {})",
+            if ok { "PASS" } else { "FAIL" },
+            r["name"].as_str().unwrap_or(""),
+            r["url"].as_str().unwrap_or(""),
+            r["detail"].as_str().unwrap_or(""),
+        );
+    }
+
+    println!("{} passed, {} failed", results.len() - failed, failed);
+    std::process::exit(if failed == 0 { 0 } else { 1 });
+}
+
+/// Whether this is a CEF subprocess rather than the browser process.
+/// main runs in every process, and only subprocesses get `--type=`.
+pub fn is_subprocess() -> bool {
+    std::env::args().any(|arg| arg.starts_with("--type="))
+}
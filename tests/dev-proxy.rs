@@ -0,0 +1,134 @@
+//! Serves the frontend through the app:// dev proxy, with a tiny HTTP
+//! server standing in for Vite or webpack-dev-server.
+
+mod common;
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+use kurogane::App;
+
+const INDEX: &str = include_str!("dev-proxy/index.html");
+
+// Address of the stand-in server, passed from the browser process to its
+// subprocesses, which inherit the environment
+const ADDR_VAR: &str = "KUROGANE_TEST_DEV_SERVER";
+
+fn main() {
+    let addr = if common::is_subprocess() {
+        std::env::var(ADDR_VAR).expect("dev server address from the browser process")
+    } else {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind stand-in dev server");
+        let addr = listener.local_addr().unwrap();
+
+        // SAFETY: no other thread has been started yet
+        unsafe { std::env::set_var(ADDR_VAR, addr.to_string()) };
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                std::thread::spawn(move || serve(stream, addr.port()));
+            }
+        });
+
+        addr.to_string()
+    };
+
+    // CEF_DEV_PROXY in the environment would take precedence
+    App::new("dev-proxy")
+        .dev_proxy(format!("http://{}", addr))
+        .command("report", common::report)
+        .run_or_exit();
+}
+
+/// Answer one request and close the connection.
+fn serve(stream: TcpStream, port: u16) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut out = stream;
+
+    let mut line = String::new();
+    if reader.read_line(&mut line).is_err() {
+        return;
+    }
+
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or("").to_string();
+    let target = parts.next().unwrap_or("/").to_string();
+
+    let mut length = 0;
+    let mut host = String::new();
+
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).unwrap_or(0) == 0 || header.trim().is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = header.trim().split_once(':') {
+            match name.to_ascii_lowercase().as_str() {
+                "content-length" => length = value.trim().parse().unwrap_or(0),
+                "host" => host = value.trim().to_string(),
+                _ => {}
+            }
+        }
+    }
+
+    let mut body = vec![0; length];
+    let _ = reader.read_exact(&mut body);
+
+    let path = target.split('?').next().unwrap_or("/");
+
+    let _ = match path {
+        "/" | "/index.html" => respond(&mut out, "200 OK", "text/html; charset=utf-8", &[], INDEX.as_bytes()),
+
+        "/data.txt" => respond(&mut out, "200 OK", "text/plain", &[], b"from the dev server"),
+
+        // Echo what arrived, so the page can check method, body and query
+        "/echo" => {
+            let echo = format!("{} {} {} {}", method, target, host, String::from_utf8_lossy(&body));
+            respond(&mut out, "200 OK", "text/plain", &[], echo.as_bytes())
+        }
+
+        // Absolute redirect to the dev server itself
+        "/redirect" => {
+            let location = format!("Location: http://127.0.0.1:{}/data.txt", port);
+            respond(&mut out, "302 Found", "text/plain", &[&location], b"")
+        }
+
+        // Chunks written with pauses, like a server-sent stream
+        "/stream" => stream_chunks(&mut out),
+
+        _ => respond(&mut out, "404 Not Found", "text/plain", &[], b"not found"),
+    };
+}
+
+fn respond(out: &mut TcpStream, status: &str, mime: &str, extra: &[&str], body: &[u8]) -> std::io::Result<()> {
+    let mut head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        status,
+        mime,
+        body.len()
+    );
+
+    for header in extra {
+        head += &format!("{}\r\n", header);
+    }
+
+    head += "\r\n";
+
+    out.write_all(head.as_bytes())?;
+    out.write_all(body)
+}
+
+fn stream_chunks(out: &mut TcpStream) -> std::io::Result<()> {
+    out.write_all(b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nTransfer-Encoding: chunked\r\n\r\n")?;
+
+    for i in 0..5 {
+        let chunk = format!("chunk {}\n", i);
+        out.write_all(format!("{:x}\r\n{}\r\n", chunk.len(), chunk).as_bytes())?;
+        out.flush()?;
+        std::thread::sleep(Duration::from_millis(100));
+    }
+
+    out.write_all(b"0\r\n\r\n")
+}
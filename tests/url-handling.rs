@@ -0,0 +1,9 @@
+mod common;
+
+use kurogane::App;
+
+fn main() {
+    App::path("url-handling")
+        .command("report", common::report)
+        .run_or_exit();
+}